@@ -73,18 +73,23 @@ function resetBoard() {
 
     var fenString = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
     if (chessPosition == "Mid Game") {
-        fenString = "r2q1rk1/1b2ppb1/1p4pp/p1nnN3/P2N4/2P5/1PBB1PPP/R2QR1K1 w KQkq - 0 1"
+        fenString = "r2q1rk1/1b2ppb1/1p4pp/p1nnN3/P2N4/2P5/1PBB1PPP/R2QR1K1 w - - 0 1"
     } else if (chessPosition == "Test Queen") {
-        fenString = "8/6R1/3n4/8/1r1Q4/8/4p1P1/K1k5 w KQkq - 0 1";
+        fenString = "8/6R1/3n4/8/1r1Q4/8/4p1P1/K1k5 w - - 0 1";
     } else if (chessPosition == "Test Checkmate") {
-        fenString = "6k1/5ppp/8/1R6/8/2K5/8/8 w KQkq - 0 1";
+        fenString = "6k1/5ppp/8/1R6/8/2K5/8/8 w - - 0 1";
     } else if (chessPosition == "Test Promotion") {
-        fenString = "5k2/1P6/8/8/3K4/8/8/8 w KQkq - 0 1";
+        fenString = "5k2/1P6/8/8/3K4/8/8/8 w - - 0 1";
     } else if (chessPosition == "Test Draw") {
-        fenString = "8/3p4/1p6/pP6/P2K1q2/7r/6k1/8 w KQkq - 0 1";
+        fenString = "8/3p4/1p6/pP6/P2K1q2/7r/6k1/8 w - - 0 1";
+    }
+    try {
+        globalGameState.set_board(fenString);
+    } catch (error) {
+        console.log("Invalid fen string %s: %s", fenString, error);
+        return;
     }
     setBoardFromFenString(fenString);
-    globalGameState.set_board(fenString);
 
     updatePlayerClocks();
 
@@ -168,10 +173,10 @@ function isPawnPromotion(pieceId, destSquareCoords) {
 function updateBoard() {
     var updated_position = globalGameState.get_board();
     setBoardFromArrayOfEnums(updated_position);
-    if (globalGameState.is_checkmate()) {
-        openGameoverPopup("Checkmate!");
-    } else if (globalGameState.is_draw()) {
-        openGameoverPopup("Draw!");
+    let result = globalGameState.get_result();
+    if (result !== undefined) {
+        openGameoverPopup(result.description());
+        result.free();
     }
 }
 
@@ -396,8 +401,10 @@ function setPiece(pieceAsFenChar, rank, file) {
 
 function makeNextMove() {
     setTimeout(() => {
-        if (globalGameState.is_checkmate() || globalGameState.is_draw()) {
+        let result = globalGameState.get_result();
+        if (result !== undefined) {
             // game is over. no next move.
+            result.free();
             return;
         }
     
@@ -0,0 +1,21 @@
+//! Compiles the games in the opening book into the binary book embedded in the
+//! engine. Run from the root of the repository after changing the games,
+//! cargo run --bin compile_book
+
+const GAMES_PATH: &str = "static/resources/opening_book.pgn";
+const BOOK_PATH: &str = "static/resources/opening_book.bin";
+
+fn main() {
+    let games = match std::fs::read_to_string(GAMES_PATH) {
+        Ok(games) => games,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", GAMES_PATH, e);
+            std::process::exit(1);
+        },
+    };
+    if let Err(e) = std::fs::write(BOOK_PATH, athena_engine::compile_opening_book(&games)) {
+        eprintln!("Failed to write {}: {}", BOOK_PATH, e);
+        std::process::exit(1);
+    }
+    println!("Compiled {} into {}", GAMES_PATH, BOOK_PATH);
+}
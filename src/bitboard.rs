@@ -0,0 +1,336 @@
+use crate::types::{Square, Color};
+
+use std::sync::OnceLock;
+
+/// Bitboards. A set of squares stored as the bits of a u64, so the squares
+/// attacked by a piece or occupied by a colour can be combined with a single
+/// and / or. Bit 0 is a1, bit 1 is b1 ... bit 63 is h8.
+/// https://www.chessprogramming.org/Bitboards
+pub type Bitboard = u64;
+
+pub const RANK_1: Bitboard = 0xff;
+pub const RANK_8: Bitboard = 0xff << 56;
+
+/// Returns an iterator over the squares set in the bitboard, from a1 to h8.
+pub fn squares(bitboard: Bitboard) -> Squares {
+    return Squares { remaining: bitboard };
+}
+
+pub struct Squares {
+    remaining: Bitboard,
+}
+
+impl Iterator for Squares {
+    type Item = Square;
+
+    fn next(&mut self) -> Option<Square> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let square_inx = self.remaining.trailing_zeros() as usize;
+        self.remaining &= self.remaining - 1;
+        return Some(Square::from_index(square_inx));
+    }
+}
+
+/// Squares attacked by a knight on the square.
+pub fn knight_attacks(square: Square) -> Bitboard {
+    return attack_tables().knight[square.index()];
+}
+
+/// Squares attacked by a king on the square.
+pub fn king_attacks(square: Square) -> Bitboard {
+    return attack_tables().king[square.index()];
+}
+
+/// Squares attacked by a pawn of the specified colour on the square. Only the
+/// diagonal captures, not the pushes.
+pub fn pawn_attacks(square: Square, color: Color) -> Bitboard {
+    return attack_tables().pawn[color.index()][square.index()];
+}
+
+/// Squares attacked by a bishop on the square. The attacks stop at, and
+/// include, the first occupied square in each direction.
+pub fn bishop_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    let tables = attack_tables();
+    return tables.bishop[square.index()].attacks(&tables.slider_attacks, occupied);
+}
+
+/// Squares attacked by a rook on the square. The attacks stop at, and
+/// include, the first occupied square in each direction.
+pub fn rook_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    let tables = attack_tables();
+    return tables.rook[square.index()].attacks(&tables.slider_attacks, occupied);
+}
+
+pub fn queen_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    return bishop_attacks(square, occupied) | rook_attacks(square, occupied);
+}
+
+/// Returns the squares strictly between two squares on the same rank, file or
+/// diagonal. Empty if the squares are not in line.
+pub fn between(a: Square, b: Square) -> Bitboard {
+    if is_rank_or_file_aligned(a, b) {
+        return rook_attacks(a, b.bit()) & rook_attacks(b, a.bit());
+    } else if is_diagonal_aligned(a, b) {
+        return bishop_attacks(a, b.bit()) & bishop_attacks(b, a.bit());
+    }
+    return 0;
+}
+
+/// Returns the whole rank, file or diagonal through two squares, from edge to
+/// edge of the board. Empty if the squares are not in line.
+pub fn line(a: Square, b: Square) -> Bitboard {
+    if is_rank_or_file_aligned(a, b) {
+        return (rook_attacks(a, 0) & rook_attacks(b, 0)) | a.bit() | b.bit();
+    } else if is_diagonal_aligned(a, b) {
+        return (bishop_attacks(a, 0) & bishop_attacks(b, 0)) | a.bit() | b.bit();
+    }
+    return 0;
+}
+
+fn is_rank_or_file_aligned(a: Square, b: Square) -> bool {
+    return a != b && (a.rank() == b.rank() || a.file() == b.file());
+}
+
+fn is_diagonal_aligned(a: Square, b: Square) -> bool {
+    return a != b && (a.rank() as i32 - b.rank() as i32).abs() == (a.file() as i32 - b.file() as i32).abs();
+}
+
+const KNIGHT_DIRECTIONS: [[i32; 2]; 8] = [[1, 2], [2, 1], [2, -1], [1, -2], [-1, -2], [-2, -1], [-2, 1], [-1, 2]];
+const KING_DIRECTIONS: [[i32; 2]; 8] = [[1, 0], [1, 1], [0, 1], [-1, 1], [-1, 0], [-1, -1], [0, -1], [1, -1]];
+const BISHOP_DIRECTIONS: [[i32; 2]; 4] = [[1, 1], [1, -1], [-1, 1], [-1, -1]];
+const ROOK_DIRECTIONS: [[i32; 2]; 4] = [[1, 0], [-1, 0], [0, 1], [0, -1]];
+
+/// Magic bitboards for the sliding pieces. The occupied squares that can block
+/// the slider are multiplied by a magic number, which maps every set of blockers
+/// to its own entry in a table of precomputed attacks.
+/// https://www.chessprogramming.org/Magic_Bitboards
+#[derive(Clone, Copy, Default)]
+struct Magic {
+    mask: Bitboard,
+    magic: u64,
+    shift: u32,
+    offset: usize,
+}
+
+impl Magic {
+    fn table_index(&self, occupied: Bitboard) -> usize {
+        return self.offset + ((occupied & self.mask).wrapping_mul(self.magic) >> self.shift) as usize;
+    }
+
+    fn attacks(&self, slider_attacks: &[Bitboard], occupied: Bitboard) -> Bitboard {
+        return slider_attacks[self.table_index(occupied)];
+    }
+}
+
+/// The precomputed attacks of every piece from every square.
+struct AttackTables {
+    knight: [Bitboard; 64],
+    king: [Bitboard; 64],
+    pawn: [[Bitboard; 64]; 2],
+    bishop: [Magic; 64],
+    rook: [Magic; 64],
+    slider_attacks: Vec<Bitboard>,
+}
+
+/// The tables are built the first time they are needed.
+static ATTACK_TABLES: OnceLock<AttackTables> = OnceLock::new();
+
+fn attack_tables() -> &'static AttackTables {
+    return ATTACK_TABLES.get_or_init(AttackTables::new);
+}
+
+impl AttackTables {
+    fn new() -> AttackTables {
+        let mut tables = AttackTables {
+            knight: [0; 64],
+            king: [0; 64],
+            pawn: [[0; 64]; 2],
+            bishop: [Magic::default(); 64],
+            rook: [Magic::default(); 64],
+            slider_attacks: vec![],
+        };
+
+        for square_inx in 0..64 {
+            let src = Square::from_index(square_inx);
+            tables.knight[square_inx] = step_attacks(src, &KNIGHT_DIRECTIONS);
+            tables.king[square_inx] = step_attacks(src, &KING_DIRECTIONS);
+            tables.pawn[0][square_inx] = step_attacks(src, &[[1, -1], [1, 1]]);
+            tables.pawn[1][square_inx] = step_attacks(src, &[[-1, -1], [-1, 1]]);
+        }
+
+        for square_inx in 0..64 {
+            tables.bishop[square_inx] = tables.add_slider(square_inx, BISHOP_MAGICS[square_inx], &BISHOP_DIRECTIONS);
+            tables.rook[square_inx] = tables.add_slider(square_inx, ROOK_MAGICS[square_inx], &ROOK_DIRECTIONS);
+        }
+
+        return tables;
+    }
+
+    /// Fills in the attacks of a slider on the square for every possible set of blockers.
+    fn add_slider(&mut self, square_inx: usize, magic_number: u64, directions: &[[i32; 2]]) -> Magic {
+        let src = Square::from_index(square_inx);
+        let mask = blocker_mask(src, directions);
+        let num_bits = mask.count_ones();
+        let magic = Magic {
+            mask,
+            magic: magic_number,
+            shift: 64 - num_bits,
+            offset: self.slider_attacks.len(),
+        };
+        self.slider_attacks.resize(magic.offset + (1 << num_bits), 0);
+
+        // Visit every subset of the mask, https://www.chessprogramming.org/Traversing_Subsets_of_a_Set
+        let mut blockers: Bitboard = 0;
+        loop {
+            self.slider_attacks[magic.table_index(blockers)] = slide_attacks(src, blockers, directions);
+            blockers = blockers.wrapping_sub(mask) & mask;
+            if blockers == 0 {
+                break;
+            }
+        }
+
+        return magic;
+    }
+}
+
+/// Returns the square a step away from src, or None if it is off the board.
+fn step(src: Square, direction: [i32; 2]) -> Option<Square> {
+    return src.offset(direction[0], direction[1]);
+}
+
+/// Squares reached by a single step in each direction. Used for knights, kings and pawns.
+fn step_attacks(src: Square, directions: &[[i32; 2]]) -> Bitboard {
+    let mut attacks: Bitboard = 0;
+    for direction in directions {
+        if let Some(dest) = step(src, *direction) {
+            attacks |= dest.bit();
+        }
+    }
+    return attacks;
+}
+
+/// Squares reached by sliding in each direction until the edge of the board or a blocker.
+fn slide_attacks(src: Square, blockers: Bitboard, directions: &[[i32; 2]]) -> Bitboard {
+    let mut attacks: Bitboard = 0;
+    for direction in directions {
+        let mut current = src;
+        while let Some(dest) = step(current, *direction) {
+            attacks |= dest.bit();
+            if blockers & dest.bit() != 0 {
+                break;
+            }
+            current = dest;
+        }
+    }
+    return attacks;
+}
+
+/// The squares that can block a slider. The last square in each direction is
+/// left out, since a piece there cannot block anything behind it.
+fn blocker_mask(src: Square, directions: &[[i32; 2]]) -> Bitboard {
+    let mut mask: Bitboard = 0;
+    for direction in directions {
+        let mut current = src;
+        while let Some(dest) = step(current, *direction) {
+            if step(dest, *direction).is_none() {
+                break;
+            }
+            mask |= dest.bit();
+            current = dest;
+        }
+    }
+    return mask;
+}
+
+/// Magic numbers that map every set of blockers on a square to a unique table
+/// entry, found by trial with sparse random numbers.
+const BISHOP_MAGICS: [u64; 64] = [
+    0x002a840401840308, 0x0002048404004000, 0x1088508106020000, 0x0604040484000420,
+    0x1002021004380001, 0x8041048240000a30, 0x4084044104103110, 0x0081004044200840,
+    0x04424110a1010901, 0x0042820841040080, 0x1001080204002c09, 0x00014804a1041815,
+    0x0004820210041001, 0x0001810403400040, 0x080240410420a084, 0x0410120201010900,
+    0x0240048504280200, 0x9402000490040325, 0x200300101c098030, 0x0004002840400800,
+    0x0002010402110140, 0x000040320100a060, 0x0042000061046000, 0x0188300084684808,
+    0x0010101108021022, 0x8724048021090c00, 0x502c0202c4080010, 0x0008082248020020,
+    0x0001020084008400, 0x0891004002082001, 0x000a021004248200, 0x00110200012a8402,
+    0x2042208430203904, 0x0c08620816111880, 0x0002045004410100, 0x0800120180180080,
+    0x0140010012c10040, 0x0050100840402400, 0x0808020080004801, 0x0004820341020100,
+    0x001a01242010c080, 0x2018620210012008, 0x80210400220a0400, 0x0020014200820801,
+    0x010008810041c400, 0x0020040880205a01, 0x001081011102e420, 0x00810604810b0208,
+    0x0000621004210094, 0x0200210802105811, 0x8008008058080200, 0x5800400084040010,
+    0x0000009002022880, 0x9000883001021010, 0x804a828404040006, 0x2010121801002800,
+    0x4012020084010846, 0x8002421202020200, 0x6104040020841000, 0x0000000205048804,
+    0x0808008041102480, 0x2305904002040440, 0x0810404282020204, 0x0588200102002100,
+];
+
+const ROOK_MAGICS: [u64; 64] = [
+    0x0a80004000801220, 0x10c0100040002000, 0x0100102000410009, 0x0b0021000c100008,
+    0x4080080080040002, 0x0200019004080200, 0x0400080a10112684, 0x20800a4d00062080,
+    0x2091800020804000, 0x0044401000200040, 0x1001002000401108, 0x1001800801100081,
+    0x0001000500080010, 0x1000808002000400, 0x0404000482100108, 0x0003000182610002,
+    0x0440848002c00420, 0x2010890040010021, 0x8800110020044300, 0x0208010100201000,
+    0x1222020004102008, 0x0000808002000400, 0x20040400094a9008, 0x0000420000804401,
+    0x0040002880004680, 0x0000200240100040, 0x0020008180201001, 0x01080080800c1000,
+    0x0104040080800800, 0x4800020080040080, 0x0002000200840108, 0x00a1000100006082,
+    0x8004400088800260, 0x0100804000802008, 0x0010008010802002, 0x000c801000800800,
+    0x0c51800402800800, 0x0002800200800400, 0x0000820804000110, 0x4003808042000401,
+    0x00208020c0018000, 0x4400402010004009, 0x22100400a800e000, 0x0e020021400a0013,
+    0x10a0080100110005, 0x0004010002004040, 0x0024080102040010, 0x4154089108420014,
+    0x0182400080002380, 0x0000400110802100, 0x0000100080200480, 0x100a000820401200,
+    0x8081004020801002, 0x0002000408100200, 0x03223a1008010c00, 0x000000831c014200,
+    0x4200208009001041, 0xc001004000881021, 0x1008200100100841, 0x0000082240920032,
+    0x4002000804201102, 0xb821000804000201, 0x4080c208102100a4, 0x02020900418c0ca2,
+];
+
+#[cfg(test)]
+mod tests {
+    use crate::bitboard;
+    use crate::types::{Square, Color, sq};
+
+    #[test]
+    fn step_attacks() {
+        assert_eq!( bitboard::knight_attacks(sq("a1")), sq("c2").bit() | sq("b3").bit() );
+        assert_eq!( bitboard::king_attacks(sq("d4")).count_ones(), 8 );
+        assert_eq!( bitboard::king_attacks(sq("h8")).count_ones(), 3 );
+        assert_eq!( bitboard::pawn_attacks(sq("a2"), Color::White), sq("b3").bit() );
+        assert_eq!( bitboard::pawn_attacks(sq("e7"), Color::Black), sq("d6").bit() | sq("f6").bit() );
+    }
+
+    #[test]
+    fn slider_attacks_match_slow_attacks() {
+        // Pseudo random blockers, checked against sliding one square at a time
+        let mut blockers: u64 = 0x9e37_79b9_7f4a_7c15;
+        for square_inx in 0..64 {
+            let src = Square::from_index(square_inx);
+            for _ in 0..16 {
+                blockers ^= blockers << 13;
+                blockers ^= blockers >> 7;
+                blockers ^= blockers << 17;
+                let occupied = blockers & blockers.rotate_left(11);
+                assert_eq!( bitboard::bishop_attacks(src, occupied),
+                            bitboard::slide_attacks(src, occupied, &bitboard::BISHOP_DIRECTIONS) );
+                assert_eq!( bitboard::rook_attacks(src, occupied),
+                            bitboard::slide_attacks(src, occupied, &bitboard::ROOK_DIRECTIONS) );
+            }
+        }
+    }
+
+    #[test]
+    fn between_and_line() {
+        assert_eq!( bitboard::between(sq("a1"), sq("d1")), sq("b1").bit() | sq("c1").bit() );
+        assert_eq!( bitboard::between(sq("b2"), sq("e5")), sq("c3").bit() | sq("d4").bit() );
+        assert_eq!( bitboard::between(sq("a1"), sq("c2")), 0 );
+        assert_eq!( bitboard::line(sq("b2"), sq("e5")), 0x8040_2010_0804_0201 );
+        assert_eq!( bitboard::line(sq("a4"), sq("c4")), 0x0000_0000_ff00_0000 );
+        assert_eq!( bitboard::line(sq("a1"), sq("c2")), 0 );
+    }
+
+    #[test]
+    fn iterate_squares() {
+        let bitboard = sq("a1").bit() | sq("e4").bit() | sq("h8").bit();
+        let squares: Vec<Square> = bitboard::squares(bitboard).collect();
+        assert_eq!( squares, vec![sq("a1"), sq("e4"), sq("h8")] );
+    }
+}
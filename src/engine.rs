@@ -1,13 +1,14 @@
 use crate::board::Board;
+use crate::book::{OpeningBook, BookSelection};
 use crate::console_log;
 use crate::evaluate::CHECKMATE_VAL;
 use crate::pieces::ChessMove;
 use crate::rules::possible_moves_from_square;
-use crate::search::{Node, alpha_beta_minimax, find_best_move, count_leaves_in_tree};
-use crate::utils::{log, coord_to_rank_file};
+use crate::types::Square;
+use crate::search::{Node, alpha_beta_minimax, find_best_move};
+use crate::utils::{log, now_ms};
 
 use crate::Math::random;
-use std::collections::LinkedList;
 
 /// Generates the best chess move from the current position.
 pub fn best_move(board: &Board, depth: usize) -> ChessMove {
@@ -15,19 +16,52 @@ pub fn best_move(board: &Board, depth: usize) -> ChessMove {
     
     // Create, evaluate and prune the search tree
     let mut root = Node::new_root(&board);
+    let mut board_copy = board.clone();
     let alpha = -CHECKMATE_VAL;
     let beta = CHECKMATE_VAL;
     let maximizing_player = board.white_to_move();
-    alpha_beta_minimax(&mut root, depth, alpha, beta, maximizing_player);
+    alpha_beta_minimax(&mut root, &mut board_copy, depth, alpha, beta, maximizing_player);
 
-    // Return the move that gives the best evaluation
-    let chess_move = find_best_move(&root);
+    // Return the move that gives the best evaluation. The search only adds moves
+    // that can be made from the position.
+    let chess_move = find_best_move(&root).to_chess_move(board).unwrap();
     
-    console_log!("    selected move, src = {:?}, dest = {:?}", chess_move.src, chess_move.dest);
+    console_log!("    selected move, src = {}, dest = {}", chess_move.src, chess_move.dest);
     
     return chess_move;
 }
 
+/// How long the engine searches for a move.
+#[derive(Clone, Copy, Debug)]
+pub enum SearchLimit {
+    /// Searches to a fixed depth in plies.
+    Depth(usize),
+    /// Searches one ply deeper at a time until the time in milliseconds has been
+    /// used. A search is never stopped part way, so the time can be exceeded.
+    Time(f64),
+}
+
+/// The deepest a search limited by time will go, for positions with very few moves.
+const MAX_TIMED_DEPTH: usize = 32;
+
+/// Generates the best chess move from the current position, searching for as long as the limit allows.
+pub fn best_move_with_limit(board: &Board, limit: SearchLimit) -> ChessMove {
+    let max_time_ms = match limit {
+        SearchLimit::Depth(depth) => return best_move(board, depth),
+        SearchLimit::Time(max_time_ms) => max_time_ms,
+    };
+
+    let start_ms = now_ms();
+    let mut depth = 1;
+    loop {
+        let chess_move = best_move(board, depth);
+        if now_ms() - start_ms >= max_time_ms || depth == MAX_TIMED_DEPTH {
+            return chess_move;
+        }
+        depth += 1;
+    }
+}
+
 /// Generates a random legal move from the current position
 pub fn random_move(board: &Board) -> ChessMove {
     console_log!("engine::random_move: ");
@@ -37,11 +71,11 @@ pub fn random_move(board: &Board) -> ChessMove {
     let mut timeout_counter = 0;
     let mut num_possible_moves = 0;
     while num_possible_moves == 0 && timeout_counter < 100 {
-        let square_rank_file = get_random_piece_to_move(&board);
-        possible_moves = possible_moves_from_square(&board, square_rank_file);
+        let square = get_random_piece_to_move(board);
+        possible_moves = possible_moves_from_square(board, square);
         num_possible_moves = possible_moves.len();
-        console_log!("    num possible moves = {}, for piece on square {:?}", 
-                     num_possible_moves, square_rank_file);
+        console_log!("    num possible moves = {}, for piece on square {}", 
+                     num_possible_moves, square);
 
         timeout_counter = timeout_counter + 1;
     }
@@ -53,73 +87,21 @@ pub fn random_move(board: &Board) -> ChessMove {
     let rand_inx = get_random_usize(num_possible_moves); 
     let chess_move = possible_moves[rand_inx];
 
-    console_log!("    selected move, src = {:?}, dest = {:?}", chess_move.src, chess_move.dest);
+    console_log!("    selected move, src = {}, dest = {}", chess_move.src, chess_move.dest);
 
     return chess_move;
 }
 
-pub fn move_from_opening_book(root: &Node, board: &Board) -> Option<ChessMove> {
-
-    let mut nodes_to_visit: LinkedList<&Node> = LinkedList::new();
-    nodes_to_visit.push_back(&root);
-    while !nodes_to_visit.is_empty() {
-        let current_node = nodes_to_visit.pop_front().unwrap();
-        for child in &current_node.children {
-            nodes_to_visit.push_back(child);
-        }
-        if board.matches(&current_node.position) {
-            let num_children = current_node.children.len();
-            let rand_child = get_random_usize(num_children);
-            return Some(current_node.children[rand_child].chess_move_from_parent);
-        }
-    }
-
-    return None;
+/// Looks up the position in the opening book, and chooses one of the moves played from it.
+pub fn move_from_opening_book(book: &OpeningBook, board: &Board, selection: BookSelection) -> Option<ChessMove> {
+    return book.choose_move(board, selection, random());
 }
 
-/// @todo! Need to use this to modify the root of the opening book. Shouldn't start
-/// from the base of the tree each time we begin searching. 
-// /// Uses non recursive BFS to find the start position in the search tree.
-// /// Modifies the root of the search tree to node with the target position.
-// fn find_position_in_book(root: &mut Node, target_board: &Board) -> &mut Node {
-
-//     {
-//         let nodes_to_visit: LinkedList<&Node> = LinkedList::new();
-//         nodes_to_visit.push_back(&root);
-//         while !nodes_to_visit.is_empty() {
-//             let current_node = nodes_to_visit.pop_front().unwrap();
-//             for child in current_node.children {
-//                 nodes_to_visit.push_back(&child);
-//             }
-//             if target_board.matches(&current_node.position) {
-//                 return current_position;
-//             }
-//         }
-//     }
-
-//     return root;
-// }
-
 /// For testing a computer that makes random but legal moves
-fn get_random_piece_to_move(board: &Board) -> [usize; 2] {
+fn get_random_piece_to_move(board: &Board) -> Square {
     console_log!("get_random_piece_to_move:");
-    // Generate random number in the range [0, 99]
-    let mut timeout_counter = 0;
-    let mut rand_occupied_rank_file = [0 as usize, 0 as usize];
-    while timeout_counter < 1000 {
-        let rank = get_random_usize(8) + 1;
-        let file = get_random_usize(8) + 1;
-
-        if (board.white_to_move() && board.is_occupied_by_white([rank, file]))|| 
-            (!board.white_to_move() && board.is_occupied_by_black([rank, file])){
-                rand_occupied_rank_file = [rank, file];
-            break;
-        }
-
-        timeout_counter = timeout_counter + 1;
-    }
-
-    return rand_occupied_rank_file;
+    let occupied_squares = board.all_occupied_squares(board.side_to_move());
+    return occupied_squares[get_random_usize(occupied_squares.len())];
 }
 
 fn get_random_usize(max: usize) -> usize {
@@ -133,16 +115,17 @@ mod tests {
     use crate::board::Board;
     use crate::pieces::ChessMove;
     use crate::engine::{best_move};
+    use crate::types::{sq, PieceKind};
 
     #[test]
     fn hanging_queen() {
 
         let mut board = Board::new();
-        board.set_board_from_fen_string("5rk1/5p1p/6p1/1q6/8/7P/5PP1/1R3RK1");
+        board.set_board_from_fen_string("5rk1/5p1p/6p1/1q6/8/7P/5PP1/1R3RK1").unwrap();
         board.render();
         let mut depth = 1 as usize;
         let mut selected_move = best_move(&board, depth);
-        let known_best_move = ChessMove::new(&board, [1, 2], [5, 2]);
+        let known_best_move = ChessMove::new(&board, sq("b1"), sq("b5"));
         assert!(selected_move.is_the_same_as(&known_best_move));
 
         depth = 2 as usize;
@@ -161,19 +144,19 @@ mod tests {
     #[test]
     fn promote_to_queen() {
         let mut board = Board::new();
-        board.set_board_from_fen_string("7k/2P5/8/8/8/8/8/K7");
+        board.set_board_from_fen_string("7k/2P5/8/8/8/8/8/K7").unwrap();
         board.render();
 
         let mut depth = 3 as usize;
         let mut selected_move = best_move(&board, depth);
-        let promote_queen = 1;
-        let mut known_best_move = ChessMove::new_promotion(&board, [7, 3], [8, 3], promote_queen);
+        let promote_queen = PieceKind::Queen;
+        let mut known_best_move = ChessMove::new_promotion(&board, sq("c7"), sq("c8"), promote_queen);
         assert!( selected_move.is_the_same_as(&known_best_move) );
 
-        board.set_board_from_fen_string("3q3k/2P5/8/8/8/8/8/K7");
+        board.set_board_from_fen_string("3q3k/2P5/8/8/8/8/8/K7").unwrap();
         board.render();
         selected_move = best_move(&board, depth);
-        known_best_move = ChessMove::new_promotion(&board, [7, 3], [8, 4], promote_queen);
+        known_best_move = ChessMove::new_promotion(&board, sq("c7"), sq("d8"), promote_queen);
         assert!( selected_move.is_the_same_as(&known_best_move) );
 
 
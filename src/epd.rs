@@ -0,0 +1,244 @@
+use crate::console_log;
+use crate::board::{Board, FenError};
+use crate::engine::{best_move_with_limit, SearchLimit};
+use crate::pieces::{ChessMove, SanError};
+
+use std::fmt;
+
+/// EpdPosition. A test position and its solution, read from one line of an
+/// EPD test suite such as
+/// "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\";"
+/// https://www.chessprogramming.org/Extended_Position_Description
+pub struct EpdPosition {
+    pub fen: String,
+    // The id opcode, the name of the position in the suite
+    pub id: Option<String>,
+    // The bm opcode, the engine solves the position by playing one of these
+    pub best_moves: Vec<ChessMove>,
+    // The am opcode, the engine solves the position by avoiding all of these
+    pub avoid_moves: Vec<ChessMove>,
+    // The c0 opcode, usually a description of the solution
+    pub comment: Option<String>,
+}
+
+impl EpdPosition {
+    /// Returns true if the move is one of the best moves, and not one of the moves to avoid.
+    pub fn is_solved_by(&self, chess_move: &ChessMove) -> bool {
+        let is_best_move = self.best_moves.is_empty() ||
+            self.best_moves.iter().any(|best_move| best_move.is_the_same_as(chess_move));
+        let is_avoid_move = self.avoid_moves.iter().any(|avoid_move| avoid_move.is_the_same_as(chess_move));
+        return is_best_move && !is_avoid_move;
+    }
+}
+
+/// EpdResult. The move the engine chose in a test position, and whether it solved it.
+#[derive(Debug, PartialEq)]
+pub struct EpdResult {
+    // The id of the position, or its fen string if it has no id
+    pub id: String,
+    // The engine's move in standard algebraic notation
+    pub engine_move: String,
+    pub solved: bool,
+}
+
+/// Reads the positions from an EPD test suite. Operations other than bm, am, id
+/// and c0 are skipped, as are empty lines and lines starting with #.
+pub fn parse_epd(epd: &str) -> Result<Vec<EpdPosition>, EpdError> {
+    let mut positions : Vec<EpdPosition> = vec![];
+
+    for (i, line) in epd.lines().enumerate() {
+        let line_num = i + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        // The first four fields are the fen string without the move counters
+        let mut operations = line;
+        let mut fen_fields : Vec<&str> = vec![];
+        for _ in 0..4 {
+            operations = operations.trim_start();
+            let end = operations.find(char::is_whitespace).unwrap_or(operations.len());
+            fen_fields.push(&operations[..end]);
+            operations = &operations[end..];
+        }
+        let fen = fen_fields.join(" ");
+        let board = match Board::from_fen_string(&fen) {
+            Ok(board) => board,
+            Err(error) => return Err(EpdError::InvalidFen { line_num, error }),
+        };
+
+        let mut position = EpdPosition {
+            fen,
+            id: None,
+            best_moves: vec![],
+            avoid_moves: vec![],
+            comment: None,
+        };
+        for (opcode, operands) in parse_operations(operations, line_num)? {
+            let read_moves = || -> Result<Vec<ChessMove>, EpdError> {
+                return operands.iter()
+                    .map(|san| ChessMove::from_san(&board, san).map_err(|error| EpdError::InvalidMove { line_num, error }))
+                    .collect();
+            };
+            match opcode.as_str() {
+                "bm" => position.best_moves = read_moves()?,
+                "am" => position.avoid_moves = read_moves()?,
+                "id" => position.id = operands.first().cloned(),
+                "c0" => position.comment = operands.first().cloned(),
+                _ => (),
+            }
+        }
+
+        if position.best_moves.is_empty() && position.avoid_moves.is_empty() {
+            return Err(EpdError::MissingSolution { line_num });
+        }
+        positions.push(position);
+    }
+
+    return Ok(positions);
+}
+
+/// Splits the operations after the fen string, such as bm Qg6; id "WAC.001";
+/// into their opcodes and operands. Quoted operands can contain spaces and semicolons.
+fn parse_operations(operations: &str, line_num: usize) -> Result<Vec<(String, Vec<String>)>, EpdError> {
+    let mut parsed : Vec<(String, Vec<String>)> = vec![];
+    let mut tokens : Vec<String> = vec![];
+    let mut token = String::new();
+    let mut in_quotes = false;
+
+    for ch in operations.chars() {
+        match ch {
+            '"' if in_quotes => {
+                tokens.push(token.clone());
+                token.clear();
+                in_quotes = false;
+            },
+            '"' => in_quotes = true,
+            _ if in_quotes => token.push(ch),
+            ';' | ' ' | '\t' => {
+                if !token.is_empty() {
+                    tokens.push(token.clone());
+                    token.clear();
+                }
+                if ch == ';' && !tokens.is_empty() {
+                    let opcode = tokens.remove(0);
+                    parsed.push((opcode, tokens.clone()));
+                    tokens.clear();
+                }
+            },
+            _ => token.push(ch),
+        }
+    }
+
+    if in_quotes {
+        return Err(EpdError::UnterminatedString { line_num });
+    }
+    // The semicolon after the last operation is sometimes left out
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    if !tokens.is_empty() {
+        let opcode = tokens.remove(0);
+        parsed.push((opcode, tokens));
+    }
+    return Ok(parsed);
+}
+
+/// Searches every position with the limit, and reports whether the engine
+/// found the solution.
+pub fn run_epd_suite(positions: &[EpdPosition], limit: SearchLimit) -> Vec<EpdResult> {
+    let mut results : Vec<EpdResult> = vec![];
+
+    for position in positions {
+        let board = Board::from_fen_string(&position.fen).unwrap();
+        let chess_move = best_move_with_limit(&board, limit);
+        let result = EpdResult {
+            id: position.id.clone().unwrap_or_else(|| position.fen.clone()),
+            engine_move: chess_move.to_san(&board),
+            solved: position.is_solved_by(&chess_move),
+        };
+
+        if !result.solved {
+            let best_moves : Vec<String> = position.best_moves.iter().map(|best_move| best_move.to_san(&board)).collect();
+            let avoid_moves : Vec<String> = position.avoid_moves.iter().map(|avoid_move| avoid_move.to_san(&board)).collect();
+            console_log!("epd::run_epd_suite: {} unsolved, played {}, bm {:?}, am {:?}, {}", result.id, result.engine_move,
+                         best_moves, avoid_moves, position.comment.as_deref().unwrap_or(""));
+        }
+        results.push(result);
+    }
+
+    let num_solved = results.iter().filter(|result| result.solved).count();
+    console_log!("epd::run_epd_suite: solved {} of {}", num_solved, results.len());
+    return results;
+}
+
+/// The reasons an EPD test suite could not be read.
+#[derive(Debug, PartialEq)]
+pub enum EpdError {
+    InvalidFen { line_num: usize, error: FenError },
+    InvalidMove { line_num: usize, error: SanError },
+    UnterminatedString { line_num: usize },
+    MissingSolution { line_num: usize },
+}
+
+impl fmt::Display for EpdError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EpdError::InvalidFen { line_num, error } =>
+                write!(f, "line {}: {}", line_num, error),
+            EpdError::InvalidMove { line_num, error } =>
+                write!(f, "line {}: {}", line_num, error),
+            EpdError::UnterminatedString { line_num } =>
+                write!(f, "line {}: a quoted operand is never closed", line_num),
+            EpdError::MissingSolution { line_num } =>
+                write!(f, "line {}: the position needs a bm or am operation to be solved", line_num),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::SearchLimit;
+    use crate::epd::{parse_epd, run_epd_suite, EpdError};
+    use crate::pieces::SanError;
+
+    #[test]
+    fn read_epd_operations() {
+        let epd = "# comment\n\n\
+                   2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\"; c0 \"mates; in 3\";\n\
+                   7k/2P5/8/8/8/8/8/K7 w - - am c8=R c8=B; hmvc 0";
+        let positions = parse_epd(epd).unwrap();
+        assert_eq!( positions.len(), 2 );
+        assert_eq!( positions[0].fen, "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - -" );
+        assert_eq!( positions[0].id.as_deref(), Some("WAC.001") );
+        assert_eq!( positions[0].comment.as_deref(), Some("mates; in 3") );
+        assert_eq!( positions[0].best_moves.len(), 1 );
+        assert_eq!( positions[1].id, None );
+        assert_eq!( positions[1].avoid_moves.len(), 2 );
+    }
+
+    #[test]
+    fn reject_invalid_epd() {
+        assert!( matches!(parse_epd("8/8/8 w - - bm Kb2;"), Err(EpdError::InvalidFen { line_num: 1, .. })) );
+        assert_eq!( parse_epd("7k/8/8/8/8/8/8/K7 w - - bm Kc3;").err(),
+                    Some(EpdError::InvalidMove { line_num: 1, error: SanError::IllegalMove("Kc3".to_string()) }) );
+        assert_eq!( parse_epd("7k/8/8/8/8/8/8/K7 w - - bm Kb2; id \"open").err(),
+                    Some(EpdError::UnterminatedString { line_num: 1 }) );
+        assert_eq!( parse_epd("\n7k/8/8/8/8/8/8/K7 w - - id \"no solution\";").err(),
+                    Some(EpdError::MissingSolution { line_num: 2 }) );
+    }
+
+    #[test]
+    fn solve_tactics_suite() {
+        let positions = parse_epd(include_str!("../tests/tactics.epd")).unwrap();
+
+        let results = run_epd_suite(&positions, SearchLimit::Depth(3));
+        let unsolved : Vec<&str> = results.iter().filter(|result| !result.solved).map(|result| result.id.as_str()).collect();
+        assert_eq!( unsolved, Vec::<&str>::new() );
+
+        // The first depth is always searched, however little time is given
+        let results = run_epd_suite(&positions[..1], SearchLimit::Time(0.0));
+        assert!( results[0].solved );
+    }
+}
@@ -1,15 +1,20 @@
 use crate::board::Board;
-use crate::pieces;
+use crate::types::Piece;
 
 pub const CHECKMATE_VAL : f32 = 1000.0;
 const CHECK_VAL : f32 = 0.5;
-const QUEEN_VAL : f32 = 9.0;
-const ROOK_VAL : f32 = 5.0;
-const BISHOP_VAL : f32 = 3.0;
-const KNIGHT_VAL : f32 = 3.0;
-const PAWN_VAL : f32 = 1.0;
+pub const QUEEN_VAL : f32 = 9.0;
+pub const ROOK_VAL : f32 = 5.0;
+pub const BISHOP_VAL : f32 = 3.0;
+pub const KNIGHT_VAL : f32 = 3.0;
+pub const PAWN_VAL : f32 = 1.0;
 
 pub fn evaluate(board : &Board) -> f32 {
+    // Neither side can win, no matter how much material they have left
+    if board.has_insufficient_material() {
+        return 0.0;
+    }
+
     let checks_score = evaluate_checks(&board);
     let material_score = evaluate_material(&board);
     return checks_score + material_score;
@@ -48,25 +53,21 @@ fn evaluate_material(board : &Board) -> f32 {
 
 fn count_pieces(board : &Board) -> NumPiecesOnBoard {
 
-    let mut pieces = NumPiecesOnBoard::new();
+    let count = |piece: char| board.pieces(Piece::from_char(piece).unwrap()).count_ones() as f32;
 
-    for i in 0..64 {
-        match board.get_piece_by_square_index(i) {
-                'p' => pieces.black_pawns += 1.0,
-                'P' => pieces.white_pawns += 1.0,
-                'n' => pieces.black_knights += 1.0,
-                'N' => pieces.white_knights += 1.0,
-                'b' => pieces.black_bishops += 1.0,
-                'B' => pieces.white_bishops += 1.0,
-                'r' => pieces.black_rooks += 1.0,
-                'R' => pieces.white_rooks += 1.0,
-                'q' => pieces.black_queens += 1.0,
-                'Q' => pieces.white_queens += 1.0,
-                'k' => pieces.black_kings += 1.0,
-                'K' => pieces.white_kings += 1.0,
-                _   => {  },
-        }
-    }
+    let mut pieces = NumPiecesOnBoard::new();
+    pieces.black_pawns = count('p');
+    pieces.white_pawns = count('P');
+    pieces.black_knights = count('n');
+    pieces.white_knights = count('N');
+    pieces.black_bishops = count('b');
+    pieces.white_bishops = count('B');
+    pieces.black_rooks = count('r');
+    pieces.white_rooks = count('R');
+    pieces.black_queens = count('q');
+    pieces.white_queens = count('Q');
+    pieces.black_kings = count('k');
+    pieces.white_kings = count('K');
 
     return pieces;
 }
@@ -115,10 +116,10 @@ mod tests {
     #[test]
     fn evaluate_material_1() {
         let mut board1 = Board::new();
-        board1.set_board_from_fen_string("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR");
+        board1.set_board_from_fen_string("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR").unwrap();
 
         let mut board2 = Board::new();
-        board2.set_board_from_fen_string("rnb1kbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR");
+        board2.set_board_from_fen_string("rnb1kbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR").unwrap();
 
         assert_eq!(evaluate_material(&board1), 0.0);
         assert!(evaluate_material(&board2) > evaluate_material(&board1));
@@ -128,11 +129,22 @@ mod tests {
     fn evaluate_checks_1() {
     
         let mut board = Board::new();
-        board.set_board_from_fen_string("6k1/1R3ppp/8/8/8/8/5PPP/2r3K1");
+        board.set_board_from_fen_string("6k1/1R3ppp/8/8/8/8/5PPP/2r3K1").unwrap();
         assert_eq!(evaluate(&board), -evaluate::CHECKMATE_VAL);
 
         let mut board = Board::new();
-        board.set_board_from_fen_string("6k1/1R3ppp/8/8/8/7P/5PP1/2r3K1");
+        board.set_board_from_fen_string("6k1/1R3ppp/8/8/8/7P/5PP1/2r3K1").unwrap();
         assert_eq!(evaluate(&board), -evaluate::CHECK_VAL);
     }
+
+    #[test]
+    fn evaluate_insufficient_material() {
+        // Up a bishop, but the position is a draw
+        let mut board = Board::new();
+        board.set_board_from_fen_string("8/8/4k3/8/8/4K3/8/5B2").unwrap();
+        assert_eq!(evaluate(&board), 0.0);
+
+        board.set_board_from_fen_string("8/8/4k3/8/8/4K3/8/5BB1").unwrap();
+        assert!(evaluate(&board) > 0.0);
+    }
 }
@@ -0,0 +1,104 @@
+use wasm_bindgen::prelude::*;
+
+/// Who won the game.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    WhiteWins,
+    BlackWins,
+    Draw,
+}
+
+/// Why the game ended.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Termination {
+    Checkmate,
+    Stalemate,
+    ThreefoldRepetition,
+    FivefoldRepetition,
+    FiftyMoveRule,
+    SeventyFiveMoveRule,
+    InsufficientMaterial,
+    Timeout,
+    Resignation,
+    Agreement,
+}
+
+/// GameResult. The outcome of a finished game and the reason it ended.
+/// Passed to the js front end as a single value.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GameResult {
+    pub outcome: Outcome,
+    pub termination: Termination,
+}
+
+impl GameResult {
+    pub fn new(outcome: Outcome, termination: Termination) -> GameResult {
+        return GameResult {
+            outcome,
+            termination,
+        };
+    }
+
+    /// The game was won by the specified colour.
+    pub fn win(is_white_winner: bool, termination: Termination) -> GameResult {
+        let outcome = if is_white_winner { Outcome::WhiteWins } else { Outcome::BlackWins };
+        return GameResult::new(outcome, termination);
+    }
+
+    pub fn draw(termination: Termination) -> GameResult {
+        return GameResult::new(Outcome::Draw, termination);
+    }
+
+    /// Reads the result at the end of a pgn game that was decided before the position
+    /// ended it. The pgn result doesn't say why the game ended, so wins are taken to be
+    /// by resignation, unless the Termination tag is "time forfeit", and draws by
+    /// agreement. Returns None if the game is unfinished.
+    pub fn from_pgn_result(result: &str, termination_tag: Option<&str>) -> Option<GameResult> {
+        let is_time_forfeit = termination_tag.is_some_and(|tag| tag.eq_ignore_ascii_case("time forfeit"));
+        let win_termination = if is_time_forfeit { Termination::Timeout } else { Termination::Resignation };
+        return match result {
+            "1-0" => Some(GameResult::win(true, win_termination)),
+            "0-1" => Some(GameResult::win(false, win_termination)),
+            "1/2-1/2" => Some(GameResult::draw(Termination::Agreement)),
+            _ => None,
+        };
+    }
+
+    /// The result as it is written at the end of a pgn game, such as 1-0.
+    pub fn pgn_result(&self) -> &'static str {
+        return match self.outcome {
+            Outcome::WhiteWins => "1-0",
+            Outcome::BlackWins => "0-1",
+            Outcome::Draw => "1/2-1/2",
+        };
+    }
+}
+
+#[wasm_bindgen]
+impl GameResult {
+    /// A message describing the result that can be shown to the user,
+    /// such as "White wins by checkmate".
+    pub fn description(&self) -> String {
+        let outcome = match self.outcome {
+            Outcome::WhiteWins => "White wins",
+            Outcome::BlackWins => "Black wins",
+            Outcome::Draw => "Draw",
+        };
+        let termination = match self.termination {
+            Termination::Checkmate => "checkmate",
+            Termination::Stalemate => "stalemate",
+            Termination::ThreefoldRepetition => "threefold repetition",
+            Termination::FivefoldRepetition => "fivefold repetition",
+            Termination::FiftyMoveRule => "the fifty move rule",
+            Termination::SeventyFiveMoveRule => "the seventy five move rule",
+            Termination::InsufficientMaterial => "insufficient material",
+            Termination::Timeout => "timeout",
+            Termination::Resignation => "resignation",
+            Termination::Agreement => "agreement",
+        };
+        return format!("{} by {}", outcome, termination);
+    }
+}
@@ -2,21 +2,37 @@ use wasm_bindgen::prelude::*;
 use js_sys::Math;
 extern crate console_error_panic_hook;
 
+mod bitboard;
 mod board;
 mod book;
 mod engine;
+mod epd;
 mod evaluate;
+mod game_result;
+mod perft;
+mod pgn;
 mod pieces;
 mod players;
+mod polyglot;
 mod search;
+mod see;
+mod types;
 mod utils;
 mod rules;
+mod zobrist;
 
-use board::Board;
+use board::{Board, UndoMove};
 use players::{Player, HumanPlayer, ComputerPlayer};
 use utils::log;
 use pieces::ChessMove;
-use utils::coord_to_rank_file;
+use types::{Square, PieceKind, Color};
+use game_result::{GameResult, Termination};
+use engine::SearchLimit;
+use polyglot::PolyglotBook;
+use book::BookSelection;
+pub use book::compile_opening_book;
+
+use std::rc::Rc;
 
 #[cfg(feature = "wee_alloc")]
 #[global_allocator]
@@ -30,6 +46,17 @@ pub struct GameState {
     board : Board,
     white_player : Box<dyn Player>,
     black_player : Box<dyn Player>,
+    // Set when the game ends away from the board, by resignation, agreement or timeout
+    result : Option<GameResult>,
+    // The fen string of the position the game started from, if it was set up with set_board
+    start_fen : Option<String>,
+    // The moves played since the start of the game, and how to take them back
+    moves : Vec<(ChessMove, UndoMove)>,
+    // The moves taken back with undo_move, the most recent last
+    undone_moves : Vec<ChessMove>,
+    // Shared by the computer players, and given to new players in set_players
+    polyglot_book : Option<Rc<PolyglotBook>>,
+    book_selection : BookSelection,
 }
 
 #[wasm_bindgen]
@@ -44,6 +71,12 @@ impl GameState {
             board: Board::new(),
             white_player: Box::new(HumanPlayer::new()),
             black_player: Box::new(ComputerPlayer::new()),
+            result: None,
+            start_fen: None,
+            moves: Vec::new(),
+            undone_moves: Vec::new(),
+            polyglot_book: None,
+            book_selection: BookSelection::RandomWeighted,
         };
     }
 
@@ -54,6 +87,11 @@ impl GameState {
         return self.board.get_current_position();
     }
 
+    /// Returns the current position as a fen string, so it can be shared or saved
+    pub fn get_fen(&self) -> String {
+        return self.board.to_fen();
+    }
+
     pub fn is_checkmate(&mut self) -> bool {
         return self.board.is_checkmate();
     }
@@ -62,57 +100,176 @@ impl GameState {
         return self.board.is_draw();
     }
 
-    pub fn set_board(&mut self, fen_string: &str) {
+    /// Returns the result of the game and the reason it ended, or undefined
+    /// if the game is still in progress.
+    pub fn get_result(&self) -> Option<GameResult> {
+        if self.result.is_some() {
+            return self.result;
+        }
+        return self.board.game_result();
+    }
+
+    /// The specified player resigns, and the other player wins.
+    pub fn resign(&mut self, is_white: bool) {
+        if self.get_result().is_none() {
+            self.result = Some(GameResult::win(!is_white, Termination::Resignation));
+        }
+    }
+
+    /// Both players agree to a draw.
+    pub fn agree_draw(&mut self) {
+        if self.get_result().is_none() {
+            self.result = Some(GameResult::draw(Termination::Agreement));
+        }
+    }
+
+    /// The specified player has run out of time, and the other player wins.
+    pub fn timeout(&mut self, is_white: bool) {
+        if self.get_result().is_none() {
+            self.result = Some(GameResult::win(!is_white, Termination::Timeout));
+        }
+    }
+
+    /// Sets the board from a fen string. If the fen string is invalid, or the
+    /// position could not occur in a legal game, the board is left unchanged
+    /// and the reasons are returned to the js front end.
+    pub fn set_board(&mut self, fen_string: &str) -> Result<(), JsValue> {
         console_log!("GameState::set_board:");
-        self.board.set_board_from_fen_string(fen_string);
+        let board = match Board::from_fen_string(fen_string) {
+            Ok(board) => board,
+            Err(e) => return Err(JsValue::from_str(&e.to_string())),
+        };
+
+        if let Err(errors) = board.validate() {
+            let reasons : Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+            return Err(JsValue::from_str(&reasons.join("; ")));
+        }
+
+        self.start_fen = Some(board.to_fen());
+        self.moves.clear();
+        self.undone_moves.clear();
+        self.board = board;
+        self.result = None;
+        return Ok(());
+    }
+
+    /// Loads the first game of a pgn file, and sets the board to the position after
+    /// its last move. If the game can't be read or replayed, the board is left
+    /// unchanged and the reason is returned to the js front end.
+    pub fn load_pgn(&mut self, pgn: &str) -> Result<(), JsValue> {
+        console_log!("GameState::load_pgn:");
+        return self.load_first_game(pgn).map_err(|e| JsValue::from_str(&e));
     }
 
     pub fn reset_board(&mut self) {
         self.board = Board::new();
+        self.result = None;
+        self.start_fen = None;
+        self.moves.clear();
+        self.undone_moves.clear();
     }
 
     pub fn is_move_legal(&mut self, src_coords: &str, dest_coords: &str) -> u8 {
         console_log!("GameState::is_move_legal: todo!");
 
-        let src_rank_file = coord_to_rank_file(src_coords);
-        let dest_rank_file = coord_to_rank_file(dest_coords);
-        let chess_move = ChessMove::new(&self.board, src_rank_file, dest_rank_file);
-        return rules::is_move_legal(&self.board, &chess_move) as u8;
+        return self.legal_move_from_coords(src_coords, dest_coords, 0).is_some() as u8;
     }
 
-    /// Enters the move entered by a human player
-    pub fn make_move(&mut self, src_coords: &str, dest_coords: &str, promotion: i32) {
+    /// Enters the move entered by a human player. Returns the move in standard
+    /// algebraic notation for the move list, or an empty string if the move is illegal.
+    pub fn make_move(&mut self, src_coords: &str, dest_coords: &str, promotion: i32) -> String {
         console_log!("GameState::make_move:");
 
-        let src_rank_file = coord_to_rank_file(src_coords);
-        let dest_rank_file = coord_to_rank_file(dest_coords);
-        let chess_move : ChessMove;
-        if promotion == 0 {
-            chess_move = ChessMove::new(&self.board, src_rank_file, dest_rank_file);
-        } else {
-            chess_move = ChessMove::new_promotion(&self.board, src_rank_file, dest_rank_file, promotion);
+        if self.get_result().is_some() {
+            console_log!("    the game is over");
+            return String::new();
+        }
+
+        let chess_move = match self.legal_move_from_coords(src_coords, dest_coords, promotion) {
+            Some(chess_move) => chess_move,
+            _ => {
+                console_log!("    illegal move, src = {}, dest = {}", src_coords, dest_coords);
+                return String::new();
+            },
+        };
+        return self.play_move(chess_move);
+    }
+
+    /// Enters a move in UCI long algebraic notation, such as e2e4 or e7e8q. Returns the
+    /// move in standard algebraic notation for the move list, or the reason it could not be made.
+    pub fn make_move_uci(&mut self, uci: &str) -> Result<String, JsValue> {
+        console_log!("GameState::make_move_uci: {}", uci);
+        if self.get_result().is_some() {
+            return Err(JsValue::from_str("the game is over"));
         }
-        self.board.make_move(chess_move);
 
+        let chess_move = match ChessMove::from_uci(&self.board, uci) {
+            Ok(chess_move) => chess_move,
+            Err(e) => return Err(JsValue::from_str(&e.to_string())),
+        };
+        return Ok(self.play_move(chess_move));
     }
 
-    /// Calculates and makes a move if it is a computer player's turn to move
-    pub fn make_computer_move(&mut self) {
+    /// Calculates and makes a move if it is a computer player's turn to move.
+    /// Returns the move in standard algebraic notation for the move list, or an
+    /// empty string if the game is over.
+    pub fn make_computer_move(&mut self) -> String {
         console_log!("lib::GameState::make_computer_move:");
-        let chess_move : ChessMove;
-        if self.board.white_to_move() {
-            chess_move = self.white_player.make_move(&self.board);
+        if self.get_result().is_some() {
+            console_log!("    the game is over");
+            return String::new();
+        }
+
+        let chess_move = if self.board.white_to_move() {
+            self.white_player.make_move(&self.board)
         } else {
-            chess_move = self.black_player.make_move(&self.board);
+            self.black_player.make_move(&self.board)
+        };
+        return self.play_move(chess_move);
+    }
+
+    /// Takes back the last move. When a human is playing the computer, the computer's
+    /// reply is taken back as well so it is the human's turn again. Returns the number
+    /// of moves taken back, so they can be removed from the move list.
+    pub fn undo_move(&mut self) -> u32 {
+        console_log!("GameState::undo_move:");
+        let mut num_undone = 0;
+        while let Some((chess_move, undo)) = self.moves.pop() {
+            self.board.unmake_move(chess_move, undo);
+            self.undone_moves.push(chess_move);
+            num_undone += 1;
+            if !self.is_computer_reply_to_human() {
+                break;
+            }
+        }
+
+        // The game continues, even if it had ended by resignation, agreement or timeout
+        if num_undone > 0 {
+            self.result = None;
         }
-        self.board.make_move(chess_move);
+        return num_undone;
+    }
+
+    /// Plays the last move taken back by undo_move again, and the computer's reply if it
+    /// was taken back with it. Returns the moves in standard algebraic notation for the
+    /// move list, which is empty if there is nothing to redo.
+    pub fn redo_move(&mut self) -> Vec<String> {
+        console_log!("GameState::redo_move:");
+        let mut redone = Vec::new();
+        while let Some(&chess_move) = self.undone_moves.last() {
+            redone.push(self.play_move(chess_move));
+            if !self.is_computer_reply_to_human() {
+                break;
+            }
+        }
+        return redone;
     }
 
     /// Returns true if it is a computer's turn to move next
     pub fn is_computer_move(&self) -> bool {
         console_log!("GameState::is_computer_move: ");
 
-        if self.board.is_checkmate() {
+        if self.get_result().is_some() {
             return false;
         }
 
@@ -126,6 +283,13 @@ impl GameState {
         return computer_move;
     }
 
+    /// Returns the game played so far in pgn format, so it can be saved.
+    pub fn get_pgn(&self) -> String {
+        let today = js_sys::Date::new_0();
+        let date = format!("{:04}.{:02}.{:02}", today.get_full_year(), today.get_month() + 1, today.get_date());
+        return self.pgn_with_date(&date);
+    }
+
     /// Renders the current gamestate board in ascii text
     pub fn render_ascii(&self) {
         self.board.render();
@@ -145,9 +309,323 @@ impl GameState {
         } else {
             self.black_player = Box::new(ComputerPlayer::new());
         }
+
+        if let Some(book) = &self.polyglot_book {
+            self.white_player.set_polyglot_book(Rc::clone(book));
+            self.black_player.set_polyglot_book(Rc::clone(book));
+        }
+        self.white_player.set_book_selection(self.book_selection);
+        self.black_player.set_book_selection(self.book_selection);
+    }
+
+    /// Sets how the computer players choose between the moves in the opening book.
+    pub fn set_book_selection(&mut self, selection: BookSelection) {
+        self.book_selection = selection;
+        self.white_player.set_book_selection(selection);
+        self.black_player.set_book_selection(selection);
+    }
+
+    /// Loads an opening book in the Polyglot .bin format for the computer players
+    /// to use, or returns the reason it could not be read.
+    pub fn load_polyglot_book(&mut self, bytes: &[u8]) -> Result<(), JsValue> {
+        console_log!("GameState::load_polyglot_book:");
+        let book = match PolyglotBook::from_bytes(bytes) {
+            Ok(book) => Rc::new(book),
+            Err(e) => return Err(JsValue::from_str(&e.to_string())),
+        };
+        self.white_player.set_polyglot_book(Rc::clone(&book));
+        self.black_player.set_polyglot_book(Rc::clone(&book));
+        self.polyglot_book = Some(book);
+        return Ok(());
     }
 
     pub fn is_white_to_move(&self) -> bool {
         return self.board.white_to_move();
     }
-}
\ No newline at end of file
+
+    /// Returns the pieces of the specified colour that the other side can win material
+    /// from by capturing. Squares are indexed in the same order as get_board.
+    pub fn get_hanging_pieces(&self, is_white: bool) -> Vec<u8> {
+        let hanging = see::hanging_pieces(&self.board, Color::from_is_white(is_white));
+        return hanging.iter().map(|square| ((8 - square.rank())*8 + square.file() - 1) as u8).collect();
+    }
+
+    /// Logs the number of perft leaf nodes under each legal move in the current
+    /// position, and returns the total. Used to debug the move generation.
+    pub fn perft_divide(&mut self, depth: u32) -> f64 {
+        let divided = perft::perft_divide(&mut self.board, depth);
+        return divided.iter().map(|(_, num_leaves)| *num_leaves as f64).sum();
+    }
+
+    /// Runs perft on each position in a perft EPD file, up to max_depth. Returns
+    /// the number of results that did not match, or the reason the file could not be read.
+    pub fn run_perft_suite(&self, epd: &str, max_depth: u32) -> Result<u32, JsValue> {
+        let positions = match perft::parse_perft_epd(epd) {
+            Ok(positions) => positions,
+            Err(e) => return Err(JsValue::from_str(&e.to_string())),
+        };
+        return Ok(perft::run_perft_suite(&positions, max_depth).len() as u32);
+    }
+
+    /// Searches each position in an EPD test suite to the depth, or for max_time_ms
+    /// milliseconds if it is more than 0. Returns the number of positions solved,
+    /// or the reason the file could not be read.
+    pub fn run_epd_suite(&self, epd: &str, depth: u32, max_time_ms: f64) -> Result<u32, JsValue> {
+        let positions = match epd::parse_epd(epd) {
+            Ok(positions) => positions,
+            Err(e) => return Err(JsValue::from_str(&e.to_string())),
+        };
+        let limit = if max_time_ms > 0.0 {
+            SearchLimit::Time(max_time_ms)
+        } else {
+            SearchLimit::Depth(depth as usize)
+        };
+        let results = epd::run_epd_suite(&positions, limit);
+        return Ok(results.iter().filter(|result| result.solved).count() as u32);
+    }
+}
+
+impl GameState {
+    /// Writes the game played so far in pgn format for get_pgn. The date is passed in,
+    /// in the pgn format such as 2024.01.31, since js_sys::Date only works in wasm.
+    fn pgn_with_date(&self, date: &str) -> String {
+        let result = match self.get_result() {
+            Some(result) => result.pgn_result(),
+            None => "*",
+        };
+
+        let mut tags = vec![
+            (String::from("Event"), String::from("Casual Game")),
+            (String::from("Site"), String::from("?")),
+            (String::from("Date"), String::from(date)),
+            (String::from("Round"), String::from("-")),
+            (String::from("White"), self.white_player.name()),
+            (String::from("Black"), self.black_player.name()),
+            (String::from("Result"), String::from(result)),
+        ];
+        if let Some(fen) = &self.start_fen {
+            tags.push((String::from("SetUp"), String::from("1")));
+            tags.push((String::from("FEN"), fen.clone()));
+        }
+
+        let mut board = match &self.start_fen {
+            Some(fen) => Board::from_fen_string(fen).unwrap(),
+            None => Board::new(),
+        };
+        let mut moves = Vec::new();
+        for (chess_move, _) in &self.moves {
+            moves.push(chess_move.to_san(&board));
+            board.make_move(*chess_move);
+        }
+
+        let game = pgn::Game { tags, moves, result: String::from(result) };
+        return game.to_pgn();
+    }
+
+    /// Loads the first game of a pgn file for load_pgn. The start position of a game
+    /// set up from a fen tag is validated in the same way as set_board.
+    fn load_first_game(&mut self, pgn: &str) -> Result<(), String> {
+        let games = match pgn::parse_pgn(pgn) {
+            Ok(games) => games,
+            Err(e) => return Err(e.to_string()),
+        };
+        let game = match games.first() {
+            Some(game) => game,
+            None => return Err("the pgn does not contain a game".to_string()),
+        };
+        let start_position = match game.start_position() {
+            Ok(board) => board,
+            Err(e) => return Err(e.to_string()),
+        };
+        if let Err(errors) = start_position.validate() {
+            let reasons : Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+            return Err(reasons.join("; "));
+        }
+        let (_, moves) = match game.replay() {
+            Ok(replayed) => replayed,
+            Err(e) => return Err(e.to_string()),
+        };
+
+        // Played again from the start so the moves can be taken back
+        self.board = start_position;
+        self.start_fen = None;
+        if game.is_set_up() {
+            self.start_fen = Some(self.board.to_fen());
+        }
+        self.moves.clear();
+        self.undone_moves.clear();
+        self.result = None;
+        for chess_move in moves {
+            self.play_move(chess_move);
+        }
+
+        // Keep the result of a game decided by resignation, agreement or time. A game
+        // ended by the position, such as by checkmate, already has its result.
+        if self.board.game_result().is_none() {
+            self.result = GameResult::from_pgn_result(&game.result, game.tag("Termination"));
+        }
+        return Ok(());
+    }
+
+    /// Finds the move entered in the web UI, or None if it is not legal. The promotion
+    /// is 1 = queen, 2 = rook, 3 = bishop, 4 = knight, or 0 for any other move.
+    fn legal_move_from_coords(&self, src_coords: &str, dest_coords: &str, promotion: i32) -> Option<ChessMove> {
+        let src = src_coords.parse::<Square>().ok()?;
+        let dest = dest_coords.parse::<Square>().ok()?;
+        if !self.board.is_occupied(src) {
+            return None;
+        }
+
+        let chess_move = match promotion {
+            0 => ChessMove::new(&self.board, src, dest),
+            1 => ChessMove::new_promotion(&self.board, src, dest, PieceKind::Queen),
+            2 => ChessMove::new_promotion(&self.board, src, dest, PieceKind::Rook),
+            3 => ChessMove::new_promotion(&self.board, src, dest, PieceKind::Bishop),
+            4 => ChessMove::new_promotion(&self.board, src, dest, PieceKind::Knight),
+            _ => return None,
+        };
+        if !rules::is_move_legal(&self.board, &chess_move) {
+            return None;
+        }
+        return Some(chess_move);
+    }
+
+    /// Makes the move on the board and records it in the game. Returns the move
+    /// in standard algebraic notation.
+    fn play_move(&mut self, chess_move: ChessMove) -> String {
+        // The moves taken back can only be redone while the same moves are played again
+        match self.undone_moves.last() {
+            Some(undone_move) if undone_move.is_the_same_as(&chess_move) => { self.undone_moves.pop(); },
+            _ => self.undone_moves.clear(),
+        }
+
+        let san = chess_move.to_san(&self.board);
+        let undo = self.board.make_move(chess_move);
+        self.moves.push((chess_move, undo));
+        return san;
+    }
+
+    /// Returns true if a human is playing the computer, and it is the computer's turn to move.
+    fn is_computer_reply_to_human(&self) -> bool {
+        if self.white_player.is_computer() == self.black_player.is_computer() {
+            return false;
+        }
+        if self.board.white_to_move() {
+            return self.white_player.is_computer();
+        }
+        return self.black_player.is_computer();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::GameState;
+    use crate::game_result::{GameResult, Termination};
+
+    #[test]
+    fn undo_and_redo_moves() {
+        let mut game_state = GameState::new();
+        game_state.set_players(0, 0);
+
+        // Includes a double pawn push, en passant and castling
+        let moves = ["e2e4", "g8f6", "e4e5", "d7d5", "e5d6", "e7d6", "g1f3", "f8e7", "f1e2", "e8g8", "e1g1"];
+        let mut fens = vec![game_state.get_fen()];
+        for uci in moves.iter() {
+            game_state.make_move_uci(uci).unwrap();
+            fens.push(game_state.get_fen());
+        }
+
+        for i in (0..moves.len()).rev() {
+            assert_eq!( game_state.undo_move(), 1 );
+            assert_eq!( game_state.get_fen(), fens[i] );
+        }
+        assert_eq!( game_state.undo_move(), 0 );
+
+        assert_eq!( game_state.redo_move(), vec!["e4"] );
+        assert_eq!( game_state.redo_move(), vec!["Nf6"] );
+        assert_eq!( game_state.get_fen(), fens[2] );
+
+        // Playing a different move means the rest can't be redone
+        game_state.make_move_uci("d2d4").unwrap();
+        assert_eq!( game_state.redo_move(), Vec::<String>::new() );
+    }
+
+    #[test]
+    fn undo_repetition_and_computer_reply() {
+        let mut game_state = GameState::new();
+        game_state.set_players(0, 0);
+        for uci in ["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1", "f6g8"].iter() {
+            game_state.make_move_uci(uci).unwrap();
+        }
+        assert!( game_state.get_result().is_some() );
+        assert_eq!( game_state.make_move("g1", "f3", 0), "" );
+        game_state.set_players(1, 1);
+        assert_eq!( game_state.make_computer_move(), "" );
+        game_state.set_players(0, 0);
+        game_state.undo_move();
+        assert!( game_state.get_result().is_none() );
+
+        // The human's move is taken back with the computer's reply
+        game_state.reset_board();
+        game_state.set_players(0, 1);
+        game_state.make_move_uci("e2e4").unwrap();
+        game_state.make_move_uci("e7e5").unwrap();
+        assert_eq!( game_state.undo_move(), 2 );
+        assert_eq!( game_state.get_fen(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1" );
+        assert_eq!( game_state.redo_move(), vec!["e4", "e5"] );
+    }
+
+    #[test]
+    fn write_game_as_pgn() {
+        let mut game_state = GameState::new();
+        game_state.set_players(0, 0);
+        for uci in ["e2e4", "e7e5", "g1f3"].iter() {
+            game_state.make_move_uci(uci).unwrap();
+        }
+        game_state.resign(false);
+        assert_eq!( game_state.pgn_with_date("2024.01.31"),
+                    "[Event \"Casual Game\"]\n[Site \"?\"]\n[Date \"2024.01.31\"]\n[Round \"-\"]\n\
+                     [White \"Human\"]\n[Black \"Human\"]\n[Result \"1-0\"]\n\n1. e4 e5 2. Nf3 1-0\n" );
+
+        // Games set up from a position are numbered from its fullmove number
+        game_state.set_players(1, 0);
+        game_state.set_board("4k3/8/8/8/8/8/4P3/4K3 b - - 0 40").unwrap();
+        game_state.make_move_uci("e8d7").unwrap();
+        game_state.make_move_uci("e2e4").unwrap();
+        assert_eq!( game_state.pgn_with_date("2024.01.31"),
+                    "[Event \"Casual Game\"]\n[Site \"?\"]\n[Date \"2024.01.31\"]\n[Round \"-\"]\n\
+                     [White \"Athena Engine\"]\n[Black \"Human\"]\n[Result \"*\"]\n\
+                     [SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 40\"]\n\n40... Kd7 41. e4 *\n" );
+    }
+
+    #[test]
+    fn load_pgn_validates_set_up_position() {
+        let mut game_state = GameState::new();
+        game_state.set_players(0, 0);
+        let pgn = "[SetUp \"1\"]\n[FEN \"8/8/8/8/8/8/8/K7 w - - 0 1\"]\n\n1. Kb2 *\n";
+        assert_eq!( game_state.load_first_game(pgn), Err("black has no king".to_string()) );
+        assert_eq!( game_state.get_fen(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1" );
+
+        let pgn = "[SetUp \"1\"]\n[FEN \"7k/8/8/8/8/8/8/K7 w - - 0 1\"]\n\n1. Kb2 *\n";
+        assert_eq!( game_state.load_first_game(pgn), Ok(()) );
+        assert_eq!( game_state.get_fen(), "7k/8/8/8/8/8/1K6/8 b - - 1 1" );
+    }
+
+    #[test]
+    fn load_pgn_result() {
+        let mut game_state = GameState::new();
+        game_state.set_players(0, 0);
+        game_state.load_first_game("1. e4 e5 2. Qh5 Nc6 0-1\n").unwrap();
+        assert_eq!( game_state.get_result(), Some(GameResult::win(false, Termination::Resignation)) );
+
+        game_state.load_first_game("[Termination \"time forfeit\"]\n\n1. e4 1-0\n").unwrap();
+        assert_eq!( game_state.get_result(), Some(GameResult::win(true, Termination::Timeout)) );
+
+        // The checkmate on the board is kept, rather than taken from the result
+        game_state.load_first_game("1. f3 e5 2. g4 Qh4# 0-1\n").unwrap();
+        assert_eq!( game_state.get_result(), Some(GameResult::win(false, Termination::Checkmate)) );
+
+        game_state.load_first_game("1. e4 *\n").unwrap();
+        assert_eq!( game_state.get_result(), None );
+    }
+}
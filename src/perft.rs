@@ -0,0 +1,241 @@
+use crate::console_log;
+use crate::board::{Board, FenError};
+use crate::pieces::ChessMove;
+use crate::rules::legal_moves;
+
+use std::fmt;
+
+/// Performance test move path enumeration. Counts the leaf nodes of the tree of
+/// legal moves to the given depth, which can be compared with known results to
+/// find bugs in the move generation.
+/// https://www.chessprogramming.org/Perft
+pub fn perft(board: &mut Board, depth: u32) -> u64 {
+
+    if depth == 0 {
+        return 1;
+    }
+
+    let moves = legal_moves(board);
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+
+    let mut total_leaves = 0;
+    for chess_move in moves {
+        let undo = board.make_move(chess_move);
+        total_leaves += perft(board, depth-1);
+        board.unmake_move(chess_move, undo);
+    }
+
+    return total_leaves;
+}
+
+/// Runs perft below each legal move in the position and prints the number of leaf
+/// nodes under each one. When the total is wrong, comparing the counts with another
+/// engine shows which move the bug is under.
+/// https://www.chessprogramming.org/Perft#Divide
+pub fn perft_divide(board: &mut Board, depth: u32) -> Vec<(ChessMove, u64)> {
+    let mut divided : Vec<(ChessMove, u64)> = vec![];
+    if depth == 0 {
+        return divided;
+    }
+
+    let mut total_leaves = 0;
+    for chess_move in legal_moves(board) {
+        let undo = board.make_move(chess_move);
+        let num_leaves = perft(board, depth-1);
+        board.unmake_move(chess_move, undo);
+
+        console_log!("{}: {}", chess_move.to_uci(), num_leaves);
+        total_leaves += num_leaves;
+        divided.push((chess_move, num_leaves));
+    }
+    console_log!("total: {}", total_leaves);
+
+    return divided;
+}
+
+/// PerftPosition. A position and its known perft results, read from one line
+/// of a perft EPD file such as
+/// "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - ;D1 14 ;D2 191 ;D3 2812"
+pub struct PerftPosition {
+    pub fen: String,
+    // The expected number of leaf nodes, as (depth, num_leaves)
+    pub expected_leaves: Vec<(u32, u64)>,
+}
+
+/// PerftFailure. A depth where the number of leaf nodes did not match the known result.
+#[derive(Debug, PartialEq)]
+pub struct PerftFailure {
+    pub fen: String,
+    pub depth: u32,
+    pub expected_leaves: u64,
+    pub num_leaves: u64,
+}
+
+/// Reads the positions from a perft EPD file. Empty lines and lines
+/// starting with # are skipped.
+pub fn parse_perft_epd(epd: &str) -> Result<Vec<PerftPosition>, PerftEpdError> {
+    let mut positions : Vec<PerftPosition> = vec![];
+
+    for (i, line) in epd.lines().enumerate() {
+        let line_num = i + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut fields = line.split(';');
+        let fen = fields.next().unwrap_or("").trim();
+        if let Err(error) = Board::from_fen_string(fen) {
+            return Err(PerftEpdError::InvalidFen { line_num, error });
+        }
+
+        let mut expected_leaves : Vec<(u32, u64)> = vec![];
+        for field in fields {
+            let field = field.trim();
+            let invalid_field = || PerftEpdError::InvalidDepthField { line_num, field: field.to_string() };
+
+            let mut parts = field.split_whitespace();
+            let depth = parts.next()
+                .and_then(|depth| depth.strip_prefix('D'))
+                .and_then(|depth| depth.parse::<u32>().ok())
+                .ok_or_else(invalid_field)?;
+            let num_leaves = parts.next()
+                .and_then(|num_leaves| num_leaves.parse::<u64>().ok())
+                .ok_or_else(invalid_field)?;
+            if parts.next().is_some() {
+                return Err(invalid_field());
+            }
+            expected_leaves.push((depth, num_leaves));
+        }
+
+        positions.push(PerftPosition {
+            fen: fen.to_string(),
+            expected_leaves,
+        });
+    }
+
+    return Ok(positions);
+}
+
+/// Runs perft on every position, skipping depths greater than max_depth since
+/// the deepest results can take a long time. Returns the depths that did not
+/// match the known results.
+pub fn run_perft_suite(positions: &[PerftPosition], max_depth: u32) -> Vec<PerftFailure> {
+    let mut failures : Vec<PerftFailure> = vec![];
+
+    for position in positions {
+        let mut board = Board::from_fen_string(&position.fen).unwrap();
+        for &(depth, expected_leaves) in &position.expected_leaves {
+            if depth > max_depth {
+                continue;
+            }
+
+            let num_leaves = perft(&mut board, depth);
+            if num_leaves != expected_leaves {
+                console_log!("FAILED {} depth {}: expected {}, found {}", position.fen, depth, expected_leaves, num_leaves);
+                failures.push(PerftFailure {
+                    fen: position.fen.clone(),
+                    depth,
+                    expected_leaves,
+                    num_leaves,
+                });
+            }
+        }
+    }
+
+    return failures;
+}
+
+/// The reasons a perft EPD file could not be read.
+#[derive(Debug, PartialEq)]
+pub enum PerftEpdError {
+    InvalidFen { line_num: usize, error: FenError },
+    InvalidDepthField { line_num: usize, field: String },
+}
+
+impl fmt::Display for PerftEpdError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PerftEpdError::InvalidFen { line_num, error } =>
+                write!(f, "line {}: {}", line_num, error),
+            PerftEpdError::InvalidDepthField { line_num, field } =>
+                write!(f, "line {}: '{}' should be a depth and a number of leaf nodes, such as 'D3 8902'", line_num, field),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::board::Board;
+    use crate::perft::{perft, perft_divide, parse_perft_epd, run_perft_suite, PerftEpdError};
+
+    // Testing the move generation using known perft results:
+    // https://www.chessprogramming.org/Perft_Results
+    #[test]
+    fn perft_test_start_pos() {
+
+        let mut board = Board::new();
+        let mut depth = 0;
+        let mut num_leaves = perft(&mut board, depth);
+        assert_eq!( num_leaves, 1 );
+
+        depth = 1;
+        num_leaves = perft(&mut board, depth);
+        assert_eq!(num_leaves, 20);
+
+        depth = 2;
+        num_leaves = perft(&mut board, depth);
+        assert_eq!(num_leaves, 400);
+
+        depth = 3;
+        num_leaves = perft(&mut board, depth);
+        assert_eq!(num_leaves, 8902);
+
+        depth = 4;
+        num_leaves = perft(&mut board, depth);
+        assert_eq!(num_leaves, 197281);
+    }
+
+    #[test]
+    fn perft_test_kiwipete() {
+
+        let mut board = Board::new();
+        board.set_board_from_fen_string("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R").unwrap();
+        let mut depth = 1;
+        let mut num_leaves = perft(&mut board, depth);
+        assert_eq!( num_leaves, 48);
+
+        depth = 2;
+        num_leaves = perft(&mut board, depth);
+        assert_eq!(num_leaves, 2039);
+    }
+
+    #[test]
+    fn perft_divide_start_pos() {
+        let mut board = Board::new();
+        let divided = perft_divide(&mut board, 3);
+        assert_eq!( divided.len(), 20 );
+        assert_eq!( divided.iter().map(|(_, num_leaves)| num_leaves).sum::<u64>(), 8902 );
+        assert!( board.matches(&Board::new()) );
+    }
+
+    #[test]
+    fn perft_suite() {
+        let positions = parse_perft_epd(include_str!("../tests/perft.epd")).unwrap();
+        assert_eq!( positions.len(), 7 );
+
+        let failures = run_perft_suite(&positions, 3);
+        assert_eq!( failures, vec![] );
+    }
+
+    #[test]
+    fn reject_invalid_perft_epd() {
+        let epd = "# comment\n\n8/8/8/8/8/8/8/K6k w - - ;D1 3 ;D2 x";
+        assert_eq!( parse_perft_epd(epd).err(),
+                    Some(PerftEpdError::InvalidDepthField { line_num: 3, field: "D2 x".to_string() }) );
+
+        assert!( matches!(parse_perft_epd("8/8/8 w - - ;D1 3"), Err(PerftEpdError::InvalidFen { line_num: 1, .. })) );
+    }
+}
@@ -0,0 +1,361 @@
+use crate::board::{Board, FenError};
+use crate::pieces::{ChessMove, SanError};
+
+use std::fmt;
+
+/// Game. One game read from a pgn file. Only the main line is kept, the
+/// comments, variations and numeric annotation glyphs are skipped.
+/// https://www.saremba.de/chessgml/standards/pgn/pgn-complete.htm
+#[derive(Clone, Debug, PartialEq)]
+pub struct Game {
+    /// The tag pairs in the order they appear, such as ("White", "Carlsen, Magnus").
+    pub tags: Vec<(String, String)>,
+    /// The moves of the main line in standard algebraic notation.
+    pub moves: Vec<String>,
+    /// The game termination marker, 1-0, 0-1, 1/2-1/2 or * if the result is unknown.
+    pub result: String,
+}
+
+impl Game {
+    /// Returns the value of the tag with the given name, if the game has it.
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        return self.tags.iter().find(|(tag_name, _)| tag_name == name).map(|(_, value)| value.as_str());
+    }
+
+    /// Returns true if the game started from the position in its FEN tag,
+    /// rather than the normal starting position.
+    pub fn is_set_up(&self) -> bool {
+        return self.tag("FEN").is_some() && self.tag("SetUp") != Some("0");
+    }
+
+    /// The position the game started from.
+    pub fn start_position(&self) -> Result<Board, PgnError> {
+        if !self.is_set_up() {
+            return Ok(Board::new());
+        }
+        return match Board::from_fen_string(self.tag("FEN").unwrap()) {
+            Ok(board) => Ok(board),
+            Err(error) => Err(PgnError::InvalidFen { error }),
+        };
+    }
+
+    /// Plays through the main line. Returns the position after the last move,
+    /// and the moves that were played.
+    pub fn replay(&self) -> Result<(Board, Vec<ChessMove>), PgnError> {
+        let mut board = self.start_position()?;
+        let mut chess_moves = Vec::new();
+        for (ply, san) in self.moves.iter().enumerate() {
+            let chess_move = match ChessMove::from_san(&board, san) {
+                Ok(chess_move) => chess_move,
+                Err(error) => return Err(PgnError::InvalidMove { ply: ply + 1, error }),
+            };
+            board.make_move(chess_move);
+            chess_moves.push(chess_move);
+        }
+        return Ok((board, chess_moves));
+    }
+
+    /// Writes the game in pgn export format, the tags followed by the numbered moves
+    /// and the result, in lines of at most 80 characters.
+    pub fn to_pgn(&self) -> String {
+        let mut pgn = String::new();
+        for (name, value) in &self.tags {
+            let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+            pgn.push_str(&format!("[{} \"{}\"]\n", name, escaped));
+        }
+        if !self.tags.is_empty() {
+            pgn.push('\n');
+        }
+
+        // Numbered from the fullmove number of the start position
+        let (mut move_num, mut white_to_move) = match self.start_position() {
+            Ok(board) => (board.fullmove_number(), board.white_to_move()),
+            Err(_) => (1, true),
+        };
+        let mut tokens = Vec::new();
+        for (i, san) in self.moves.iter().enumerate() {
+            if white_to_move {
+                tokens.push(format!("{}.", move_num));
+            } else if i == 0 {
+                tokens.push(format!("{}...", move_num));
+            }
+            tokens.push(san.clone());
+            if !white_to_move {
+                move_num += 1;
+            }
+            white_to_move = !white_to_move;
+        }
+        tokens.push(self.result.clone());
+
+        let mut line_len = 0;
+        for token in tokens {
+            if line_len > 0 && line_len + 1 + token.len() > 80 {
+                pgn.push('\n');
+                line_len = 0;
+            } else if line_len > 0 {
+                pgn.push(' ');
+                line_len += 1;
+            }
+            pgn.push_str(&token);
+            line_len += token.len();
+        }
+        pgn.push('\n');
+        return pgn;
+    }
+}
+
+const RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+
+/// Reads every game in a pgn file. A game ends at its result, or where the tags
+/// of the next game start. Moves are not checked until the game is replayed.
+pub fn parse_pgn(pgn: &str) -> Result<Vec<Game>, PgnError> {
+    let mut games = Vec::new();
+    let mut game = Game { tags: Vec::new(), moves: Vec::new(), result: String::new() };
+    let mut variation_depth = 0;
+    let mut line_num = 1;
+    let mut chars = pgn.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\n' => line_num += 1,
+            _ if ch.is_whitespace() => (),
+            // An escaped line, used by some programs to store their own data
+            '%' => while chars.next_if(|&next| next != '\n').is_some() {},
+            ';' => while chars.next_if(|&next| next != '\n').is_some() {},
+            '{' => {
+                let start_line_num = line_num;
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some('\n') => line_num += 1,
+                        Some(_) => (),
+                        None => return Err(PgnError::UnterminatedComment { line_num: start_line_num }),
+                    }
+                }
+            },
+            '(' => variation_depth += 1,
+            ')' => {
+                if variation_depth == 0 {
+                    return Err(PgnError::UnbalancedVariation { line_num });
+                }
+                variation_depth -= 1;
+            },
+            '$' => while chars.next_if(|next| next.is_ascii_digit()).is_some() {},
+            '[' => {
+                if variation_depth > 0 {
+                    return Err(PgnError::UnbalancedVariation { line_num });
+                }
+                let mut tag = String::new();
+                let mut in_quotes = false;
+                loop {
+                    match chars.next() {
+                        Some(']') if !in_quotes => break,
+                        Some('"') => {
+                            in_quotes = !in_quotes;
+                            tag.push('"');
+                        },
+                        // Keep the escape so the value can be unescaped in parse_tag
+                        Some('\\') if in_quotes => {
+                            tag.push('\\');
+                            tag.extend(chars.next());
+                        },
+                        Some('\n') | None => return Err(PgnError::InvalidTag { line_num, tag }),
+                        Some(next) => tag.push(next),
+                    }
+                }
+                // The tags of the next game start before the last game had a result
+                if !game.moves.is_empty() {
+                    game.result = result_from_tag(&game);
+                    games.push(game);
+                    game = Game { tags: Vec::new(), moves: Vec::new(), result: String::new() };
+                }
+                match parse_tag(&tag) {
+                    Some(tag_pair) => game.tags.push(tag_pair),
+                    None => return Err(PgnError::InvalidTag { line_num, tag }),
+                }
+            },
+            _ => {
+                let mut token = ch.to_string();
+                while let Some(next) = chars.next_if(|next| !next.is_whitespace() && !"{}()[];$".contains(*next)) {
+                    token.push(next);
+                }
+                if variation_depth > 0 {
+                    continue;
+                }
+
+                if RESULTS.contains(&token.as_str()) {
+                    game.result = token;
+                    games.push(game);
+                    game = Game { tags: Vec::new(), moves: Vec::new(), result: String::new() };
+                    continue;
+                }
+
+                // Move numbers such as 12. or 12... can be written next to the move
+                let san = if token.starts_with(|first: char| first.is_ascii_digit()) {
+                    token.rsplit('.').next().unwrap_or("")
+                } else {
+                    token.as_str()
+                };
+                if !san.is_empty() && san != "e.p." {
+                    game.moves.push(san.to_string());
+                }
+            },
+        }
+    }
+
+    if variation_depth > 0 {
+        return Err(PgnError::UnbalancedVariation { line_num });
+    }
+    if !game.tags.is_empty() || !game.moves.is_empty() {
+        game.result = result_from_tag(&game);
+        games.push(game);
+    }
+    return Ok(games);
+}
+
+/// The result of a game whose movetext ends without one, from its Result tag.
+fn result_from_tag(game: &Game) -> String {
+    let result = game.tag("Result").filter(|result| RESULTS.contains(result));
+    return result.unwrap_or("*").to_string();
+}
+
+/// Reads the inside of a tag such as [Event "F/S Return Match"].
+fn parse_tag(tag: &str) -> Option<(String, String)> {
+    let (name, value) = tag.trim().split_once(char::is_whitespace)?;
+    let quoted = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+    if name.is_empty() || !name.chars().all(|ch| ch.is_ascii_alphanumeric() || ch == '_') {
+        return None;
+    }
+
+    let mut value = String::new();
+    let mut chars = quoted.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => value.extend(chars.next()),
+            '"' => return None,
+            _ => value.push(ch),
+        }
+    }
+    return Some((name.to_string(), value));
+}
+
+/// The reasons a pgn file could not be read, or a game in it could not be replayed.
+#[derive(Debug, PartialEq)]
+pub enum PgnError {
+    InvalidTag { line_num: usize, tag: String },
+    UnterminatedComment { line_num: usize },
+    UnbalancedVariation { line_num: usize },
+    InvalidFen { error: FenError },
+    InvalidMove { ply: usize, error: SanError },
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PgnError::InvalidTag { line_num, tag } =>
+                write!(f, "line {}: '[{}' should be a tag such as [Event \"Casual Game\"]", line_num, tag),
+            PgnError::UnterminatedComment { line_num } =>
+                write!(f, "line {}: the comment is never closed with a '}}'", line_num),
+            PgnError::UnbalancedVariation { line_num } =>
+                write!(f, "line {}: the brackets of the variations do not match", line_num),
+            PgnError::InvalidFen { error } =>
+                write!(f, "FEN tag: {}", error),
+            PgnError::InvalidMove { ply, error } =>
+                write!(f, "half move {}: {}", ply, error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pgn::{parse_pgn, Game, PgnError};
+    use crate::pieces::SanError;
+
+    #[test]
+    fn read_tags_comments_and_variations() {
+        let pgn = r#"
+[Event "F/S Return Match"]
+[Site "Belgrade, Serbia JUG"]
+[Date "1992.11.04"]
+[Round "29"]
+[White "Fischer, Robert J."]
+[Black "Spassky, Boris V."]
+[Result "1/2-1/2"]
+[Annotator "A \"quoted\" name"]
+
+1. e4 e5 2. Nf3 Nc6 3. Bb5 {This opening is called the Ruy Lopez.} 3... a6 $1
+4. Ba4 (4. Bxc6 dxc6 (4... bxc6?!) 5. O-O) 4...Nf6 ; the main line
+5. O-O! 1/2-1/2
+
+[Event "Second game"]
+1. d4 d5 *
+"#;
+        let games = parse_pgn(pgn).unwrap();
+        assert_eq!( games.len(), 2 );
+        assert_eq!( games[0].tag("White"), Some("Fischer, Robert J.") );
+        assert_eq!( games[0].tag("Annotator"), Some("A \"quoted\" name") );
+        assert_eq!( games[0].tag("ECO"), None );
+        assert_eq!( games[0].moves, vec!["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Ba4", "Nf6", "O-O!"] );
+        assert_eq!( games[0].result, "1/2-1/2" );
+        assert_eq!( games[1].moves, vec!["d4", "d5"] );
+        assert_eq!( games[1].result, "*" );
+
+        let (board, chess_moves) = games[0].replay().unwrap();
+        assert_eq!( chess_moves.len(), 9 );
+        assert_eq!( board.to_fen(), "r1bqkb1r/1ppp1ppp/p1n2n2/4p3/B3P3/5N2/PPPP1PPP/RNBQ1RK1 b kq - 3 5" );
+    }
+
+    #[test]
+    fn read_games_without_tags() {
+        let games = parse_pgn(include_str!("../static/resources/test_games_2.pgn")).unwrap();
+        assert_eq!( games.len(), 2 );
+        assert!( games.iter().all(|game| game.tags.is_empty() && game.replay().is_ok()) );
+
+        // Ends without a result, so it is taken from the tag
+        let games = parse_pgn("[Result \"0-1\"]\n1. f3 e5 2. g4 Qh4#").unwrap();
+        assert_eq!( games[0].result, "0-1" );
+        assert!( games[0].replay().unwrap().0.is_checkmate() );
+    }
+
+    #[test]
+    fn replay_from_set_up_position() {
+        let pgn = "[SetUp \"1\"]\n[FEN \"4k3/8/4K3/8/8/8/8/7R w - - 0 40\"]\n\n40. Rh8# 1-0";
+        let games = parse_pgn(pgn).unwrap();
+        assert!( games[0].is_set_up() );
+        assert!( games[0].replay().unwrap().0.is_checkmate() );
+
+        let games = parse_pgn("[FEN \"4k3/8/8\"]\n*").unwrap();
+        assert!( matches!(games[0].replay(), Err(PgnError::InvalidFen { .. })) );
+    }
+
+    #[test]
+    fn write_pgn() {
+        let games = parse_pgn(include_str!("../static/resources/test_games_1.pgn")).unwrap();
+        let pgn = games[0].to_pgn();
+        assert!( pgn.starts_with("1. e4 c5 2. Nf3 d6 3. d4 cxd4") );
+        assert!( pgn.lines().all(|line| line.len() <= 80) );
+        assert_eq!( parse_pgn(&pgn).unwrap(), games );
+
+        // Numbered from the start position, and tag values are escaped
+        let game = Game {
+            tags: vec![(String::from("White"), String::from("A \"quoted\" name")),
+                       (String::from("FEN"), String::from("4k3/8/4K3/8/8/8/8/R7 b - - 0 40"))],
+            moves: vec![String::from("Kf8"), String::from("Ra8#")],
+            result: String::from("1-0"),
+        };
+        assert_eq!( game.to_pgn(), "[White \"A \\\"quoted\\\" name\"]\n[FEN \"4k3/8/4K3/8/8/8/8/R7 b - - 0 40\"]\n\n40... Kf8 41. Ra8# 1-0\n" );
+        assert_eq!( parse_pgn(&game.to_pgn()).unwrap(), vec![game] );
+    }
+
+    #[test]
+    fn reject_invalid_pgn() {
+        assert_eq!( parse_pgn("1. e4 {unclosed\n comment"), Err(PgnError::UnterminatedComment { line_num: 1 }) );
+        assert_eq!( parse_pgn("1. e4 (1. d4 *"), Err(PgnError::UnbalancedVariation { line_num: 1 }) );
+        assert_eq!( parse_pgn("\n1. e4 ) *"), Err(PgnError::UnbalancedVariation { line_num: 2 }) );
+        assert_eq!( parse_pgn("[Event Casual]"), Err(PgnError::InvalidTag { line_num: 1, tag: "Event Casual".to_string() }) );
+
+        let games = parse_pgn("1. e4 e5 2. Ke3 *").unwrap();
+        assert_eq!( games[0].replay().err(), Some(PgnError::InvalidMove { ply: 3, error: SanError::IllegalMove("Ke3".to_string()) }) );
+        assert_eq!( games[0].replay().err().unwrap().to_string(), "half move 3: 'Ke3' is not a legal move in this position" );
+    }
+}
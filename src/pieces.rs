@@ -1,27 +1,11 @@
 use crate::console_log;
-use crate::utils::{log, coord_to_rank_file};
+use crate::utils::log;
 use crate::board::Board;
-use crate::rules::is_move_legal;
+use crate::bitboard::{self, Bitboard};
+use crate::rules::legal_moves;
+use crate::types::{Square, Piece, PieceKind, Color};
 
-/// DeltaRankFile. Defines one possible piece movement as a 
-/// change in rank and file from the current square. 
-pub struct DeltaRankFile {
-    delta_rank: i32, 
-    delta_file: i32,
-}
-
-impl DeltaRankFile {
-    /// Returns an invalid square [0, 0] if destination square is off the board
-    pub fn dest_from_src(&self, src: [usize; 2]) -> [usize; 2] {
-        let dest = [(src[0] as i32 + self.delta_rank),
-                   (src[1] as i32 + self.delta_file)];
-        if dest[0] > 8 || dest[0] < 1 || dest[1] > 8 || dest[1] < 1 {
-            return [0, 0];
-        }
-        
-        return [dest[0] as usize, dest[1] as usize];
-    }
-}
+use std::fmt;
 
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum MoveType {
@@ -33,237 +17,290 @@ pub enum MoveType {
     PromoteToRook,
     PromoteToBishop,
     PromoteToKnight,
-    Invalid,
+}
+
+impl MoveType {
+    /// The move type for a promotion to the kind of piece. Pawns can only promote
+    /// to a queen, rook, bishop or knight.
+    pub fn promotion(kind: PieceKind) -> MoveType {
+        return match kind {
+            PieceKind::Queen => MoveType::PromoteToQueen,
+            PieceKind::Rook => MoveType::PromoteToRook,
+            PieceKind::Bishop => MoveType::PromoteToBishop,
+            PieceKind::Knight => MoveType::PromoteToKnight,
+            _ => panic!("a pawn can not promote to a {:?}", kind),
+        };
+    }
+
+    /// The kind of piece a pawn promotes to, or None if the move is not a promotion.
+    pub fn promotion_kind(self) -> Option<PieceKind> {
+        return match self {
+            MoveType::PromoteToQueen => Some(PieceKind::Queen),
+            MoveType::PromoteToRook => Some(PieceKind::Rook),
+            MoveType::PromoteToBishop => Some(PieceKind::Bishop),
+            MoveType::PromoteToKnight => Some(PieceKind::Knight),
+            _ => None,
+        };
+    }
 }
 
 /// ChessMove, represents a move made by a player
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug)]
 pub struct ChessMove {
-    pub src: [usize; 2],
-    pub dest: [usize; 2],
-    pub piece: char,
+    pub src: Square,
+    pub dest: Square,
+    pub piece: Piece,
     pub move_type: MoveType,
 }
 
 impl ChessMove {
-    pub fn new(board: &Board, src: [usize; 2], dest: [usize; 2]) -> ChessMove {
-        let mut new_move = ChessMove::new_empty_move();
-        new_move.set_move(&board, src, dest);
-        new_move.move_type = MoveType::Standard;
-        return new_move;
-    }
+    /// Creates the move of the piece on the src square. Castling and en passant are
+    /// found from the position. Panics if the src square is empty.
+    pub fn new(board: &Board, src: Square, dest: Square) -> ChessMove {
+        let piece = match board.get_piece_on_square(src) {
+            Some(piece) => piece,
+            None => panic!("ChessMove::new: no piece on {}", src),
+        };
+
+        let mut move_type = MoveType::Standard;
+        if piece.kind == PieceKind::King && src.file() == 5 {
+            if dest.file() == 7 {
+                move_type = MoveType::CastleKingSide;
+            } else if dest.file() == 3 {
+                move_type = MoveType::CastleQueenSide;
+            }
+        }
 
-    pub fn new_empty_move() -> ChessMove {
-        return ChessMove {
-            src: [0, 0],
-            dest: [0, 0],
-            piece: '-',
-            move_type: MoveType::Invalid,
+        if piece.kind == PieceKind::Pawn && board.get_en_passant_square() == Some(dest) {
+            move_type = MoveType::EnPassant;
         }
+
+        return ChessMove {
+            src,
+            dest,
+            piece,
+            move_type,
+        };
     }
 
-    pub fn new_promotion(board: &Board, src: [usize; 2], dest: [usize; 2], promotion_enum: i32) -> ChessMove {
-        let mut new_move = ChessMove::new(&board, src, dest);
-        if promotion_enum == 1 {
-            new_move.move_type = MoveType::PromoteToQueen;
-        } else if promotion_enum == 2 {
-            new_move.move_type = MoveType::PromoteToRook;
-        } else if promotion_enum == 3 {
-            new_move.move_type = MoveType::PromoteToBishop;
-        } else if promotion_enum == 4 {
-            new_move.move_type = MoveType::PromoteToKnight;
-        } else {
-            // SHould never call this method without a valid promotion.
-            assert!(false);
-        }
-        return new_move; 
+    pub fn new_promotion(board: &Board, src: Square, dest: Square, promotion: PieceKind) -> ChessMove {
+        let mut new_move = ChessMove::new(board, src, dest);
+        new_move.move_type = MoveType::promotion(promotion);
+        return new_move;
     }
 
-    pub fn set_move(&mut self, board: &Board, src: [usize; 2], dest: [usize; 2]) {
-        
-        self.src = src;
-        self.dest = dest;
-        self.piece = board.get_piece_on_square(src);
-
-        if (self.piece == 'k' || self.piece == 'K') && src[1] == 5 {
-            if dest[1] == 7 {
-                self.move_type = MoveType::CastleKingSide;
-            } else if dest[1] == 3 {
-                self.move_type = MoveType::CastleQueenSide;
-            }
+    /// The square of the piece captured by the move. The destination square, except
+    /// for en passant where the captured pawn is beside the moving pawn.
+    pub fn captured_square(self) -> Square {
+        if self.move_type == MoveType::EnPassant {
+            return Square::new(self.src.rank(), self.dest.file()).unwrap();
         }
+        return self.dest;
+    }
 
-        if (self.piece == 'p' || self.piece == 'P') && 
-            dest[0] == board.get_en_passant_square()[0] && 
-            dest[1] == board.get_en_passant_square()[1] {
-            self.move_type = MoveType::EnPassant;
+    /// Reads a move in standard algebraic notation, such as Nbd7, exd5, e8=Q+ or O-O,
+    /// and finds the legal move it describes in the position. Check and mate marks,
+    /// annotations like ! and ?, a trailing e.p. and the 0-0 spelling of castling are
+    /// accepted. Source squares only need to be given when the move is ambiguous.
+    pub fn from_san(board: &Board, san: &str) -> Result<ChessMove, SanError> {
+        let notation = strip_san_suffixes(san.trim());
+        if notation.is_empty() {
+            return Err(SanError::Empty);
         }
 
-        if ((self.piece == 'p' && dest[0] == 1) || 
-            (self.piece == 'P' && dest[0] == 8)) {
-
+        let legal_moves = legal_moves(board);
+        let castle_type = match notation {
+            "O-O" | "0-0" => Some(MoveType::CastleKingSide),
+            "O-O-O" | "0-0-0" => Some(MoveType::CastleQueenSide),
+            _ => None,
+        };
+        if let Some(castle_type) = castle_type {
+            return match legal_moves.into_iter().find(|chess_move| chess_move.move_type == castle_type) {
+                Some(chess_move) => Ok(chess_move),
+                None => Err(SanError::IllegalMove(san.to_string())),
+            };
         }
-    }
 
-    pub fn set_move_from_pgn_notation(&mut self, board: &Board, pgn_notation: &String, white_to_move: bool) {
-        
-        if pgn_notation == "O-O" {
-            self.move_type = MoveType::CastleKingSide;
-            if white_to_move {
-                self.piece = 'K';
-                self.src = [1, 5];
-                self.dest = [1, 7];
-            } else {
-                self.piece = 'k';
-                self.src = [8, 5];
-                self.dest = [8, 7];
-            }
-        } else if pgn_notation == "O-O-O" {
-            self.move_type = MoveType::CastleQueenSide;
-            if white_to_move {
-                self.piece = 'K';
-                self.src = [1, 5];
-                self.dest = [1, 3];
-            } else {
-                self.piece = 'k';
-                self.src = [8, 5];
-                self.dest = [8, 3];
+        let invalid_syntax = || SanError::InvalidSyntax(san.to_string());
+        let mut chars : Vec<char> = notation.chars().collect();
+
+        // The piece letter, which is left off for pawns
+        let kind = match chars[0] {
+            'N' | 'B' | 'R' | 'Q' | 'K' => PieceKind::from_char(chars.remove(0)).unwrap(),
+            _ => PieceKind::Pawn,
+        };
+
+        // The promotion piece, usually written e8=Q, but sometimes e8Q
+        let mut promotion : Option<PieceKind> = None;
+        if let Some(&last) = chars.last() {
+            if "QRBN".contains(last) {
+                promotion = PieceKind::from_char(last);
+                chars.pop();
+                if chars.last() == Some(&'=') {
+                    chars.pop();
+                }
             }
-        } else if (pgn_notation.as_bytes()[0] as char).is_ascii_lowercase() {
-            // Pawn Moves
+        }
+        if promotion.is_some() && kind != PieceKind::Pawn {
+            return Err(invalid_syntax());
+        }
 
-            // Ignore checks
-            let mut mut_move_notation = pgn_notation.clone();
-            if mut_move_notation.ends_with('+') {
-                mut_move_notation.pop();
+        // The destination square
+        if chars.len() < 2 {
+            return Err(invalid_syntax());
+        }
+        let dest_coord : String = chars[chars.len()-2..].iter().collect();
+        let dest = match dest_coord.parse::<Square>() {
+            Ok(dest) => dest,
+            Err(_) => return Err(invalid_syntax()),
+        };
+        chars.truncate(chars.len()-2);
+
+        // Whatever is left is the capture mark and the source file and / or rank
+        if chars.last() == Some(&'x') || chars.last() == Some(&':') {
+            chars.pop();
+        }
+        let mut src_file : Option<usize> = None;
+        let mut src_rank : Option<usize> = None;
+        for ch in chars {
+            if ('a'..='h').contains(&ch) && src_file.is_none() && src_rank.is_none() {
+                src_file = Some((ch as u8 - b'a' + 1) as usize);
+            } else if ('1'..='8').contains(&ch) && src_rank.is_none() {
+                src_rank = Some((ch as u8 - b'0') as usize);
+            } else {
+                return Err(invalid_syntax());
             }
+        }
 
-            if (pgn_notation.as_bytes()[1] as char).is_ascii_digit() {
-                // Normal pawn moves
-
-                self.move_type = MoveType::Standard;
-                self.dest = coord_to_rank_file(&pgn_notation[..2]);
-                if white_to_move {
-                    self.piece = 'P';
-                    self.src = [self.dest[0] - 1, self.dest[1]];
-                    if !board.is_occupied(self.src) {
-                        self.src[0] -= 1;
-                    }
-                } else {
-                    self.piece = 'p';
-                    self.src = [self.dest[0] + 1, self.dest[1]];
-                    if !board.is_occupied(self.src) {
-                        self.src[0] += 1;
-                    }
-                }
-            } else if (pgn_notation.as_bytes()[1] as char) == 'x' {
-                // Pawn capture moves
-
-                let n = mut_move_notation.len();
-                let clarified_file = ( mut_move_notation.as_bytes()[0] - b'a' + 1) as usize;
-                self.dest = coord_to_rank_file(String::from_utf8(mut_move_notation.as_bytes()[n-2..n].to_vec()).unwrap().as_str());
-                
-                if self.dest[0] == board.get_en_passant_square()[0] &&
-                   self.dest[1] == board.get_en_passant_square()[1] {
-    
-                    self.move_type = MoveType::EnPassant;
-                } else {
-                    self.move_type = MoveType::Standard;
-                }
+        let matching_moves : Vec<ChessMove> = legal_moves.into_iter().filter(|chess_move| {
+            let promotion_kind = chess_move.move_type.promotion_kind();
+            chess_move.piece.kind == kind &&
+                chess_move.dest == dest &&
+                src_file.map_or(true, |file| chess_move.src.file() == file) &&
+                src_rank.map_or(true, |rank| chess_move.src.rank() == rank) &&
+                promotion.map_or(true, |promotion| promotion_kind == Some(promotion)) &&
+                (promotion_kind.is_some() || promotion.is_none())
+        }).collect();
+
+        return match matching_moves.len() {
+            0 => Err(SanError::IllegalMove(san.to_string())),
+            1 => Ok(matching_moves[0]),
+            _ if kind == PieceKind::Pawn && promotion.is_none() => Err(SanError::MissingPromotion(san.to_string())),
+            _ => Err(SanError::AmbiguousMove(san.to_string())),
+        };
+    }
 
-                if white_to_move {
-                    self.src = [self.dest[0] - 1, clarified_file];
-                    self.piece = 'P';
-                } else {
-                    self.src = [self.dest[0] + 1, clarified_file];
-                    self.piece = 'p';
-                }
+    /// Reads a move in the long algebraic notation used by the UCI protocol, the source
+    /// and destination squares followed by the promotion piece, such as e2e4 or e7e8q.
+    /// Castling is written as the king's move, e1g1, though moving the king onto its
+    /// own rook, e1h1, is also accepted.
+    pub fn from_uci(board: &Board, uci: &str) -> Result<ChessMove, UciError> {
+        let notation = uci.trim();
+        let invalid_syntax = || UciError::InvalidSyntax(uci.to_string());
+        if !notation.is_ascii() || (notation.len() != 4 && notation.len() != 5) {
+            return Err(invalid_syntax());
+        }
 
-            } else {
-                console_log!("  UNRECOGNISED PAWN MOVE = {:?}", pgn_notation);
-                self.move_type = MoveType::Invalid;
-                return;
-            }
-            
-            if pgn_notation.contains('=') {
-                // Promotions
-
-                let promotion = mut_move_notation.pop().unwrap();
-                if promotion == 'Q' {
-                    self.move_type = MoveType::PromoteToQueen;
-                } else if promotion == 'R' {
-                    self.move_type = MoveType::PromoteToRook;
-                } else if promotion == 'B' {
-                    self.move_type = MoveType::PromoteToBishop;
-                } else if promotion == 'N' {
-                    self.move_type = MoveType::PromoteToKnight;
+        let src = notation[..2].parse::<Square>().map_err(|_| invalid_syntax())?;
+        let mut dest = notation[2..4].parse::<Square>().map_err(|_| invalid_syntax())?;
+        let promotion = match notation.chars().nth(4) {
+            None => None,
+            Some(ch) if "qrbn".contains(ch) => PieceKind::from_char(ch),
+            Some(_) => return Err(invalid_syntax()),
+        };
+
+        if let Some(piece) = board.get_piece_on_square(src) {
+            let own_rook = Some(Piece::new(piece.color, PieceKind::Rook));
+            if piece.kind == PieceKind::King && src.file() == 5 && board.get_piece_on_square(dest) == own_rook {
+                if dest.file() == 8 {
+                    dest = Square::new(dest.rank(), 7).unwrap();
+                } else if dest.file() == 1 {
+                    dest = Square::new(dest.rank(), 3).unwrap();
                 }
             }
+        }
 
-        } else if (pgn_notation.as_bytes()[0] as char).is_ascii_uppercase() {
-            // Knights, Bishops, Rooks, Kings, Queens
+        let matching_moves : Vec<ChessMove> = legal_moves(board).into_iter().filter(|chess_move| {
+            let promotion_kind = chess_move.move_type.promotion_kind();
+            chess_move.src == src && chess_move.dest == dest &&
+                promotion.map_or(true, |promotion| promotion_kind == Some(promotion)) &&
+                (promotion_kind.is_some() || promotion.is_none())
+        }).collect();
+
+        return match matching_moves.len() {
+            0 => Err(UciError::IllegalMove(uci.to_string())),
+            1 => Ok(matching_moves[0]),
+            _ => Err(UciError::MissingPromotion(uci.to_string())),
+        };
+    }
 
-            if white_to_move {
-                self.piece = pgn_notation.as_bytes()[0] as char;
-            } else {
-                self.piece = (pgn_notation.as_bytes()[0] as char).to_ascii_lowercase();
-            }
+    /// Writes the move in the long algebraic notation used by the UCI protocol, such as e2e4 or e7e8q.
+    pub fn to_uci(self) -> String {
+        let mut uci = format!("{}{}", self.src, self.dest);
+        if let Some(promotion) = self.move_type.promotion_kind() {
+            uci.push(promotion.to_char().to_ascii_lowercase());
+        }
+        return uci;
+    }
 
-            let mut mut_move_notation = pgn_notation.clone();
+    /// Writes the move in standard algebraic notation, such as Nbd7, exd5, e8=Q+ or O-O.
+    /// The board must be the position before the move is made. The source square is
+    /// only given when another piece of the same type could also move to the destination.
+    /// https://en.wikipedia.org/wiki/Algebraic_notation_(chess)
+    pub fn to_san(self, board: &Board) -> String {
+        let mut san = match self.move_type {
+            MoveType::CastleKingSide => String::from("O-O"),
+            MoveType::CastleQueenSide => String::from("O-O-O"),
+            _ => self.to_san_without_suffix(board),
+        };
+
+        let mut position = board.clone();
+        position.make_move(self);
+        if position.is_checkmate() {
+            san.push('#');
+        } else if position.is_check() {
+            san.push('+');
+        }
+        return san;
+    }
 
-            // Ignore checks
-            if mut_move_notation.ends_with('+') {
-                mut_move_notation.pop();
-            }
-            let mut n = mut_move_notation.len();
-            self.dest = coord_to_rank_file(String::from_utf8(mut_move_notation.as_bytes()[n-2..n].to_vec()).unwrap().as_str());
-
-            // Remove the destination coords and ignore capture mark
-            mut_move_notation.pop();
-            mut_move_notation.pop();
-            if mut_move_notation.ends_with('x') {
-                mut_move_notation.pop();
-            }
+    fn to_san_without_suffix(self, board: &Board) -> String {
+        let mut san = String::new();
+        let is_capture = board.is_occupied(self.dest) || self.move_type == MoveType::EnPassant;
+        let src_coord = self.src.to_string();
+        let (src_file, src_rank) = src_coord.split_at(1);
 
-            // Need to disambiguate white piece is being specified
-            let mut clarified_rank : usize = 0;
-            let mut clarified_file : usize = 0;
-            n = mut_move_notation.len();
-            if n == 2 && (mut_move_notation.as_bytes()[n-1] as char).is_ascii_digit() {
-                // disambiguate by rank
-                clarified_rank = ( mut_move_notation.as_bytes()[n-1] - b'0' ) as usize;
-            } else if n == 2 && (mut_move_notation.as_bytes()[n-1] as char).is_ascii_lowercase() {
-                // disambiguate by file
-                clarified_file = ( mut_move_notation.as_bytes()[n-1] - b'a' + 1) as usize;
-            } else if n == 3 {
-                // disambiguate by rank and file
-                clarified_rank = ( mut_move_notation.as_bytes()[n-1] - b'0' ) as usize;
-                clarified_file = ( mut_move_notation.as_bytes()[n-2] - b'a' + 1) as usize;
+        if self.piece.kind == PieceKind::Pawn {
+            if is_capture {
+                san.push_str(src_file);
             }
-
-            let attacking_moves = pieces_attacking_square(&board, self.dest, white_to_move);
-            for attacking_move in attacking_moves {
-                if attacking_move.piece == self.piece && (
-                    (clarified_file == 0 && clarified_rank == 0 ) ||
-                    (clarified_file == 0 && clarified_rank == attacking_move.src[0]) ||
-                    (clarified_rank == 0 && clarified_file == attacking_move.src[1]) ||
-                    (clarified_file == attacking_move.src[0] && clarified_rank == attacking_move.src[1])) &&
-                    is_move_legal(&board, &attacking_move) {
-
-                    self.src = attacking_move.src;
-                    break;
+        } else {
+            san.push(self.piece.kind.to_char());
+
+            let ambiguous_moves : Vec<ChessMove> = legal_moves(board).into_iter()
+                .filter(|other| other.piece == self.piece && other.dest == self.dest && other.src != self.src)
+                .collect();
+            if !ambiguous_moves.is_empty() {
+                if ambiguous_moves.iter().all(|other| other.src.file() != self.src.file()) {
+                    san.push_str(src_file);
+                } else if ambiguous_moves.iter().all(|other| other.src.rank() != self.src.rank()) {
+                    san.push_str(src_rank);
+                } else {
+                    san.push_str(&src_coord);
                 }
             }
+        }
 
-            if self.src[0] != 0 && self.src[1] != 0 {
-                // Otherwise src square was not found, move remains invalid
-                self.move_type = MoveType::Standard;
-            }
+        if is_capture {
+            san.push('x');
+        }
+        san.push_str(&self.dest.to_string());
 
-        } else {
-            console_log!("  UNRECOGNISED PGN NOTATION = {:?}", pgn_notation);
-            self.move_type = MoveType::Invalid;
+        if let Some(promotion) = self.move_type.promotion_kind() {
+            san.push('=');
+            san.push(promotion.to_char());
         }
+        return san;
     }
 
     pub fn is_the_same_as(&self, that: &ChessMove) -> bool {
@@ -274,340 +311,336 @@ impl ChessMove {
         let is_same_move_type = self.move_type == that.move_type;
         return is_same_piece && is_same_squares && is_same_move_type;
     }
-
-    pub fn is_white_piece(&self) -> bool {
-        return self.piece.is_uppercase();
-    }
 }
 
-/// Returns all possible pawn moves from a given square
-pub fn pawn_moves(board: &Board, rank_file: [usize; 2], is_white: bool) -> Vec<ChessMove> {
-    let mut possible_moves_from_square = pawn_capture_moves(&board, rank_file, is_white);
-    possible_moves_from_square.append(&mut pawn_non_capture_moves(&board, rank_file, is_white));
-    return possible_moves_from_square;
-}
+/// PackedMove. A move packed into 16 bits, for storing moves in the search tree
+/// and the opening book. Bits 0-5 are the source square, bits 6-11 the destination
+/// square and bits 12-14 the move type. The piece is not stored, it is found on the
+/// source square when the move is unpacked.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct PackedMove(u16);
 
-/// Returns all possible pawn captures from a given square
-fn pawn_capture_moves(board: &Board, src_rank_file: [usize; 2], is_white: bool) -> Vec<ChessMove> {
-    let mut capture_moves : Vec<ChessMove> = vec![];
+impl PackedMove {
+    pub fn src(self) -> Square {
+        return Square::from_index((self.0 & 0x3f) as usize);
+    }
 
-    let capture_movements;
-    if is_white {
-        capture_movements = vec![
-            DeltaRankFile {delta_rank:  1, delta_file: -1},
-            DeltaRankFile {delta_rank:  1, delta_file:  1},
-        ];
-    } else {
-        capture_movements = vec![
-            DeltaRankFile {delta_rank:  -1, delta_file: -1},
-            DeltaRankFile {delta_rank:  -1, delta_file:  1},
-        ];
-    }
-
-    for capture_movement in capture_movements {
-        let dest_rank_file = capture_movement.dest_from_src(src_rank_file);
-        if board.is_valid_rank_file(dest_rank_file) &&
-           (is_capture(&board, dest_rank_file, is_white) || 
-            (dest_rank_file[0] == board.get_en_passant_square()[0] && 
-            dest_rank_file[1] == board.get_en_passant_square()[1])) {
-
-            // Is this move a promotion?
-            if ( is_white && dest_rank_file[0] == 8 ) ||
-               ( !is_white && dest_rank_file[0] == 1 ) {
- 
-                let mut promotions = vec![ChessMove::new_promotion(&board, src_rank_file, dest_rank_file, 1),
-                                          ChessMove::new_promotion(&board, src_rank_file, dest_rank_file, 2),
-                                          ChessMove::new_promotion(&board, src_rank_file, dest_rank_file, 3),
-                                          ChessMove::new_promotion(&board, src_rank_file, dest_rank_file, 4)];
-                capture_moves.append(&mut promotions);
-            } else {
-                let standard_move = ChessMove::new(&board, src_rank_file, dest_rank_file);
-                capture_moves.push(standard_move);
-            }
-        }
+    pub fn dest(self) -> Square {
+        return Square::from_index(((self.0 >> 6) & 0x3f) as usize);
     }
 
-    return capture_moves;
+    pub fn move_type(self) -> MoveType {
+        return match self.0 >> 12 {
+            0 => MoveType::Standard,
+            1 => MoveType::CastleKingSide,
+            2 => MoveType::CastleQueenSide,
+            3 => MoveType::EnPassant,
+            4 => MoveType::PromoteToQueen,
+            5 => MoveType::PromoteToRook,
+            6 => MoveType::PromoteToBishop,
+            _ => MoveType::PromoteToKnight,
+        };
+    }
+
+    /// Unpacks the move in the position it was made from. Returns None if the source
+    /// square is empty, so the move can't have been made from this position.
+    pub fn to_chess_move(self, board: &Board) -> Option<ChessMove> {
+        let piece = board.get_piece_on_square(self.src())?;
+        return Some(ChessMove {
+            src: self.src(),
+            dest: self.dest(),
+            piece,
+            move_type: self.move_type(),
+        });
+    }
 }
 
-/// Returns all possible non capture pawn moves from a given square. 
-fn pawn_non_capture_moves(board: &Board, src_rank_file: [usize; 2], is_white: bool) -> Vec<ChessMove> {
-    let mut non_capture_moves : Vec<ChessMove> = vec![];
+impl From<ChessMove> for PackedMove {
+    fn from(chess_move: ChessMove) -> PackedMove {
+        let flags : u16 = match chess_move.move_type {
+            MoveType::Standard => 0,
+            MoveType::CastleKingSide => 1,
+            MoveType::CastleQueenSide => 2,
+            MoveType::EnPassant => 3,
+            MoveType::PromoteToQueen => 4,
+            MoveType::PromoteToRook => 5,
+            MoveType::PromoteToBishop => 6,
+            MoveType::PromoteToKnight => 7,
+        };
+        return PackedMove(chess_move.src.index() as u16 | (chess_move.dest.index() as u16) << 6 | flags << 12);
+    }
+}
 
-    let non_capture_movements;
-    if is_white {
-        non_capture_movements = vec![
-            DeltaRankFile {delta_rank:  1, delta_file:  0},
-            DeltaRankFile {delta_rank:  2, delta_file:  0},
-        ];
-    } else {
-        non_capture_movements = vec![
-            DeltaRankFile {delta_rank:  -1, delta_file:  0},
-            DeltaRankFile {delta_rank:  -2, delta_file:  0},
-        ];
+/// The raw 16 bits, for storing the move in a binary file.
+impl From<PackedMove> for u16 {
+    fn from(packed_move: PackedMove) -> u16 {
+        return packed_move.0;
     }
+}
 
-    for non_capture_movement in non_capture_movements {
-        let dest_rank_file = non_capture_movement.dest_from_src(src_rank_file);
+impl From<u16> for PackedMove {
+    fn from(bits: u16) -> PackedMove {
+        return PackedMove(bits);
+    }
+}
 
-        if !board.is_valid_rank_file(dest_rank_file) ||
-           board.is_occupied(dest_rank_file) {
-            continue;
+/// Removes the check and mate marks, annotations and en passant mark from the end of a move.
+fn strip_san_suffixes(san: &str) -> &str {
+    let mut notation = san;
+    loop {
+        let stripped = notation.trim_end_matches(|ch| "+#!?".contains(ch)).trim_end();
+        let stripped = stripped.strip_suffix("e.p.").unwrap_or(stripped).trim_end();
+        if stripped == notation {
+            return notation;
         }
-        if is_slide_clear_for_non_capture(&board, src_rank_file, dest_rank_file, is_white, false) {
+        notation = stripped;
+    }
+}
 
-            // Is this move a promotion?
-            if ( is_white && dest_rank_file[0] == 8 ) ||
-               ( !is_white && dest_rank_file[0] == 1 ) {
+/// Describes why a move in standard algebraic notation could not be read.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SanError {
+    Empty,
+    InvalidSyntax(String),
+    IllegalMove(String),
+    AmbiguousMove(String),
+    MissingPromotion(String),
+}
 
-                let mut promotions = vec![ChessMove::new_promotion(&board, src_rank_file, dest_rank_file, 1),
-                                          ChessMove::new_promotion(&board, src_rank_file, dest_rank_file, 2),
-                                          ChessMove::new_promotion(&board, src_rank_file, dest_rank_file, 3),
-                                          ChessMove::new_promotion(&board, src_rank_file, dest_rank_file, 4)];
-                non_capture_moves.append(&mut promotions);
-            } else {
-                let standard_move = ChessMove::new(&board, src_rank_file, dest_rank_file);
-                non_capture_moves.push(standard_move);
-            }
+impl fmt::Display for SanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SanError::Empty => write!(f, "move is empty"),
+            SanError::InvalidSyntax(san) =>
+                write!(f, "'{}' is not a move in standard algebraic notation", san),
+            SanError::IllegalMove(san) =>
+                write!(f, "'{}' is not a legal move in this position", san),
+            SanError::AmbiguousMove(san) =>
+                write!(f, "'{}' could be more than one legal move, the source square is needed", san),
+            SanError::MissingPromotion(san) =>
+                write!(f, "'{}' moves a pawn to the last rank, the promotion piece is needed", san),
         }
     }
-
-    return non_capture_moves;
 }
 
-/// Returns all knight moves from a given square
-pub fn knight_moves(board: &Board, src_rank_file: [usize; 2], is_white: bool) -> Vec<ChessMove> {
-    let mut possible_moves_from_square : Vec<ChessMove> = vec![];
+/// Describes why a move in UCI long algebraic notation could not be read.
+#[derive(Clone, Debug, PartialEq)]
+pub enum UciError {
+    InvalidSyntax(String),
+    IllegalMove(String),
+    MissingPromotion(String),
+}
 
-    let movements = vec![
-        DeltaRankFile { delta_rank: -1, delta_file:  2, },
-        DeltaRankFile { delta_rank:  1, delta_file:  2, },
-        DeltaRankFile { delta_rank:  1, delta_file: -2, },
-        DeltaRankFile { delta_rank: -1, delta_file: -2, },
-        DeltaRankFile { delta_rank: -2, delta_file:  1, },
-        DeltaRankFile { delta_rank:  2, delta_file:  1, },
-        DeltaRankFile { delta_rank:  2, delta_file: -1, },
-        DeltaRankFile { delta_rank: -2, delta_file: -1, },
-    ];
-
-    for movement in movements {
-        let dest_rank_file = movement.dest_from_src(src_rank_file);
-        if board.is_valid_rank_file(src_rank_file) && board.is_valid_rank_file(dest_rank_file) && 
-           (!board.is_occupied(dest_rank_file) || is_capture(&board, dest_rank_file, is_white) ) {
-            let possible_move = ChessMove::new(&board, src_rank_file, dest_rank_file);
-            possible_moves_from_square.push(possible_move);
+impl fmt::Display for UciError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UciError::InvalidSyntax(uci) =>
+                write!(f, "'{}' is not a move in UCI notation, expected a move such as e2e4 or e7e8q", uci),
+            UciError::IllegalMove(uci) =>
+                write!(f, "'{}' is not a legal move in this position", uci),
+            UciError::MissingPromotion(uci) =>
+                write!(f, "'{}' moves a pawn to the last rank, the promotion piece is needed", uci),
         }
     }
+}
 
+/// Returns all possible pawn moves from a given square
+pub fn pawn_moves(board: &Board, square: Square, color: Color) -> Vec<ChessMove> {
+    let mut possible_moves_from_square = pawn_capture_moves(board, square, color);
+    possible_moves_from_square.append(&mut pawn_non_capture_moves(board, square, color));
     return possible_moves_from_square;
 }
 
-/// Returns all the bishop moves from a given square
-pub fn bishop_moves(board: &Board, src: [usize; 2], is_white: bool) -> Vec<ChessMove> {
-    let movements = vec![
-        DeltaRankFile { delta_rank: -1, delta_file:  1, },
-        DeltaRankFile { delta_rank:  1, delta_file:  1, },
-        DeltaRankFile { delta_rank:  1, delta_file: -1, },
-        DeltaRankFile { delta_rank: -1, delta_file: -1, },
-    ];
-
-    return slide_moves(movements, &board, src, is_white);
-}
+/// Returns all possible pawn captures from a given square
+fn pawn_capture_moves(board: &Board, src: Square, color: Color) -> Vec<ChessMove> {
+    let mut capture_moves : Vec<ChessMove> = vec![];
 
-/// Returns all possible rook moves from a given square
-pub fn rook_moves(board: &Board, src: [usize; 2], is_white: bool) -> Vec<ChessMove> {
-    let movements = vec![
-        DeltaRankFile { delta_rank: -1, delta_file:  0, },
-        DeltaRankFile { delta_rank:  0, delta_file:  1, },
-        DeltaRankFile { delta_rank:  0, delta_file: -1, },
-        DeltaRankFile { delta_rank:  1, delta_file:  0, },
-    ];
-
-    return slide_moves(movements, &board, src, is_white);
-}
+    let mut capture_squares = capturable_squares(board, color);
+    if let Some(en_passant_sq) = board.get_en_passant_square() {
+        capture_squares |= en_passant_sq.bit();
+    }
 
+    let targets = bitboard::pawn_attacks(src, color) & capture_squares;
+    for dest in bitboard::squares(targets) {
+        capture_moves.append(&mut pawn_moves_to_square(board, src, dest, color));
+    }
 
-/// Generates all possible moves from a square by sliding in the given directions.
-/// Used to generate the bishop and rook moves
-fn slide_moves(movements: Vec<DeltaRankFile>, board: &Board, src: [usize; 2], is_white: bool)-> Vec<ChessMove> {
-    let mut possible_moves_from_square : Vec<ChessMove> = vec![];
+    return capture_moves;
+}
 
-    for movement in movements {
-        let mut dest = movement.dest_from_src(src);
-        while board.is_valid_rank_file(src) && board.is_valid_rank_file(dest) && 
-              (!board.is_occupied(dest) || is_capture(&board, dest, is_white) ) {
+/// Returns all possible non capture pawn moves from a given square. 
+fn pawn_non_capture_moves(board: &Board, src: Square, color: Color) -> Vec<ChessMove> {
+    let mut non_capture_moves : Vec<ChessMove> = vec![];
 
-            let possible_move = ChessMove::new(&board, src, dest);
-            possible_moves_from_square.push(possible_move);
-            
-            if is_capture(&board, dest, is_white) {
-                break;
-            }
+    // Pawns can only move two squares from their starting rank
+    let direction = color.pawn_direction();
+    let starting_rank = if color.is_white() { 2 } else { 7 };
+    let num_squares = if src.rank() == starting_rank { 2 } else { 1 };
+
+    for i in 1..=num_squares {
+        let dest = match src.offset(i*direction, 0) {
+            Some(dest) if !board.is_occupied(dest) => dest,
+            _ => break,
+        };
+        non_capture_moves.append(&mut pawn_moves_to_square(board, src, dest, color));
+    }
 
-            dest = movement.dest_from_src(dest);
-        }
+    return non_capture_moves;
+}
+
+/// The pawn move from src to dest, or the four promotions if the pawn reaches the last rank.
+fn pawn_moves_to_square(board: &Board, src: Square, dest: Square, color: Color) -> Vec<ChessMove> {
+    if dest.rank() == color.opposite().back_rank() {
+        return PieceKind::PROMOTIONS.iter()
+            .map(|&promotion| ChessMove::new_promotion(board, src, dest, promotion))
+            .collect();
     }
+    return vec![ChessMove::new(board, src, dest)];
+}
 
-    return possible_moves_from_square;
+/// Returns all knight moves from a given square
+pub fn knight_moves(board: &Board, src: Square, color: Color) -> Vec<ChessMove> {
+    let targets = bitboard::knight_attacks(src) & 
+                  (!board.occupied() | capturable_squares(board, color));
+    return moves_to_targets(board, src, targets);
+}
+
+/// Returns all the bishop moves from a given square
+pub fn bishop_moves(board: &Board, src: Square, color: Color) -> Vec<ChessMove> {
+    let targets = bitboard::bishop_attacks(src, board.occupied()) & 
+                  (!board.occupied() | capturable_squares(board, color));
+    return moves_to_targets(board, src, targets);
 }
 
+/// Returns all possible rook moves from a given square
+pub fn rook_moves(board: &Board, src: Square, color: Color) -> Vec<ChessMove> {
+    let targets = bitboard::rook_attacks(src, board.occupied()) & 
+                  (!board.occupied() | capturable_squares(board, color));
+    return moves_to_targets(board, src, targets);
+}
 
 /// Returns all possible queen moves from a given square
-pub fn queen_moves(board: &Board, src: [usize; 2], is_white: bool) -> Vec<ChessMove> {
-    let mut possible_moves_from_square = rook_moves(&board, src, is_white);
-    possible_moves_from_square.append(&mut bishop_moves(&board, src, is_white));
-    return possible_moves_from_square;
+pub fn queen_moves(board: &Board, src: Square, color: Color) -> Vec<ChessMove> {
+    let targets = bitboard::queen_attacks(src, board.occupied()) & 
+                  (!board.occupied() | capturable_squares(board, color));
+    return moves_to_targets(board, src, targets);
 }
 
 /// Returns all possible queen moves from a given square
-pub fn king_moves(board: &Board, src: [usize; 2], is_white: bool) -> Vec<ChessMove> {
+pub fn king_moves(board: &Board, src: Square, color: Color) -> Vec<ChessMove> {
     let move_into_check_allowed = false;
-    let mut possible_moves_from_square = king_standard_moves(board, src, is_white, move_into_check_allowed);
-    possible_moves_from_square.append(&mut king_castle_moves(board, src, is_white));
+    let mut possible_moves_from_square = king_standard_moves(board, src, color, move_into_check_allowed);
+    possible_moves_from_square.append(&mut king_castle_moves(board, src, color));
     return possible_moves_from_square;
 }
 
 /// Returns legal standard king moves from the current position
-pub fn king_standard_moves(board: &Board, src: [usize; 2], is_white: bool, move_into_check_allowed: bool) -> Vec<ChessMove> {
+pub fn king_standard_moves(board: &Board, src: Square, color: Color, move_into_check_allowed: bool) -> Vec<ChessMove> {
     let mut standard_moves : Vec<ChessMove> = vec![];
-    let movements = vec![
-        DeltaRankFile { delta_rank: -1, delta_file:  0, },
-        DeltaRankFile { delta_rank: -1, delta_file:  1, },
-        DeltaRankFile { delta_rank:  0, delta_file:  1, },
-        DeltaRankFile { delta_rank:  1, delta_file:  1, },
-        DeltaRankFile { delta_rank:  1, delta_file:  0, },
-        DeltaRankFile { delta_rank:  1, delta_file: -1, },
-        DeltaRankFile { delta_rank:  0, delta_file: -1, },
-        DeltaRankFile { delta_rank: -1, delta_file: -1, },
-    ];
-
-    if !board.is_valid_rank_file(src) {
-        return standard_moves;
-    }
-
-    // Create a copy of the board without the king, otherwise the king's current position can
-    // cover potential checks.
-    let mut board_copy = board.clone();
-    board_copy.clear_square(src);
 
-    for movement in movements {
-        let dest = movement.dest_from_src(src);
-        if !board.is_valid_rank_file(dest) {
-            continue;
-        }
+    // Remove the king from the occupied squares, otherwise the king's current position can
+    // cover potential checks.
+    let occupied_without_king = board.occupied() & !src.bit();
 
-        if !move_into_check_allowed && is_square_attacked(&board_copy, dest, !is_white) {
+    let targets = bitboard::king_attacks(src) & !board.occupied_by(color);
+    for dest in bitboard::squares(targets) {
+        if !move_into_check_allowed && attackers_of_square(board, dest, color.opposite(), occupied_without_king) != 0 {
             // King should not move into check
             continue;
         }
-        
-        if !board.is_occupied(dest) || is_capture_including_king_capture(&board_copy, dest, is_white) {
-            let possible_move = ChessMove::new(&board, src, dest);
-            standard_moves.push(possible_move);
-        }
+
+        let possible_move = ChessMove::new(board, src, dest);
+        standard_moves.push(possible_move);
     }
 
     return standard_moves;
 }
 
 /// Returns legal castle moves from the current position
-pub fn king_castle_moves(board: &Board, src: [usize; 2], is_white: bool) -> Vec<ChessMove> {
+pub fn king_castle_moves(board: &Board, src: Square, color: Color) -> Vec<ChessMove> {
     let mut possible_castle_moves : Vec<ChessMove> = vec![];
 
-    if is_square_attacked(&board, src, !is_white) {
+    if is_square_attacked(board, src, color.opposite()) {
         // no castle moves avaliable when king is in check
         return possible_castle_moves;        
     }
 
     // Check if the king 
-    if board.is_castle_king_side_avaliable(is_white) { 
-        let dest: [usize; 2];
-        if is_white {
-            dest = [1, 7];
-        } else {
-            dest = [8, 7];
-        }
+    if board.is_castle_king_side_avaliable(color) { 
+        let dest = Square::new(color.back_rank(), 7).unwrap();
         
         let is_king = true;
-        if is_slide_clear_for_non_capture(&board, src, dest, is_white, is_king) {
-            let castle_move = ChessMove::new(&board, src, dest);
+        if !board.is_occupied(dest) && 
+           is_slide_clear_for_non_capture(board, src, dest, color, is_king) {
+            let castle_move = ChessMove::new(board, src, dest);
             possible_castle_moves.push(castle_move);
         }
 
     }
     
-    if board.is_castle_queen_side_avaliable(is_white) {
-        let dest: [usize; 2];
-        if is_white {
-            dest = [1, 3];
-        } else {
-            dest = [8, 3];
-        }
+    if board.is_castle_queen_side_avaliable(color) {
+        let dest = Square::new(color.back_rank(), 3).unwrap();
 
+        // The square next to the rook must also be empty, but the king does not cross it
         let is_king = true;
-        if is_slide_clear_for_non_capture(&board, src, dest, is_white, is_king) {
-            let castle_move = ChessMove::new(&board, src, dest);
+        if !board.is_occupied(dest) && !board.is_occupied(Square::new(dest.rank(), 2).unwrap()) && 
+           is_slide_clear_for_non_capture(board, src, dest, color, is_king) {
+            let castle_move = ChessMove::new(board, src, dest);
             possible_castle_moves.push(castle_move);
         }
     }
     return possible_castle_moves;
 }
 
-/// Returns all possible king standard moves without checking if the king moves into check.
-/// Used to ensure the kings will never be next to each other
-fn king_moves_move_into_check_allowed(board: &Board, src: [usize; 2], is_white: bool) -> Vec<ChessMove> {
-    let move_into_check_allowed = true;
-    return king_standard_moves(&board, src, is_white, move_into_check_allowed);
-}
-
-/// Checks if a square is occupied by a piece of a different colour, and if it is 
-/// occupied by the king. The king can never be captured.
-fn is_capture(board : &Board, dest_rank_file: [usize; 2], is_white: bool) -> bool {
-    return ((is_white && board.is_occupied_by_black(dest_rank_file)) ||
-           (!is_white && board.is_occupied_by_white(dest_rank_file)) ) && 
-           !board.is_occupied_by_king(dest_rank_file);
+/// Returns the squares a piece of the specified colour can capture on. Squares occupied
+/// by the other colour, except for the king. The king can never be captured.
+fn capturable_squares(board: &Board, color: Color) -> Bitboard {
+    let enemy_king = Piece::new(color.opposite(), PieceKind::King);
+    return board.occupied_by(color.opposite()) & !board.pieces(enemy_king);
 }
 
-/// Performs the same checks as is_capture, but allows the king to be captured. Used to
-/// check which squares are under attack in the is_square_attacked function
-fn is_capture_including_king_capture(board : &Board, dest_rank_file: [usize; 2], is_white: bool) -> bool {
-    return ( is_white && board.is_occupied_by_black(dest_rank_file)) ||
-           (!is_white && board.is_occupied_by_white(dest_rank_file)); 
+/// Creates a move from the src square to each of the target squares.
+fn moves_to_targets(board: &Board, src: Square, targets: Bitboard) -> Vec<ChessMove> {
+    let mut possible_moves_from_square : Vec<ChessMove> = vec![];
+    for dest in bitboard::squares(targets) {
+        possible_moves_from_square.push(ChessMove::new(board, src, dest));
+    }
+    return possible_moves_from_square;
 }
 
 /// Checks if a slide move is clear of other pieces. Slide moves
 /// handle movements for rooks, bishops, queens and king castles
-fn is_slide_clear_for_non_capture(board: &Board, src: [usize; 2], dest: [usize; 2], 
-    is_white: bool, is_king: bool) -> bool {
-    let rank_dir = (dest[0] as i32 - src[0] as i32).signum();
-    let file_dir = (dest[1] as i32 - src[1] as i32).signum();
-    let mut traversed = [(src[0] as i32 + rank_dir) as usize,
-                        (src[1] as i32 + file_dir) as usize];
-
-    while traversed[0] != dest[0] || traversed[1] != dest[1] {
-
-        if board.is_occupied(traversed) {
-            return false;
+fn is_slide_clear_for_non_capture(board: &Board, src: Square, dest: Square, 
+    color: Color, is_king: bool) -> bool {
+    let rank_dir = (dest.rank() as i32 - src.rank() as i32).signum();
+    let file_dir = (dest.file() as i32 - src.file() as i32).signum();
+    let mut traversed = src.offset(rank_dir, file_dir);
+
+    while let Some(square) = traversed {
+        if square == dest {
+            break;
         }
 
-        // The king cannot enter check
-        if is_king && is_square_attacked(&board, traversed, !is_white) {
+        if board.is_occupied(square) {
             return false;
         }
 
-        // Check if we have traversed past the edge of the board
-        if !board.is_valid_rank_file(traversed) {
-            console_log!("[pieces::is_slide_clear]: ERROR! Tried to check a square off the edge of the board.");
+        // The king cannot enter check
+        if is_king && is_square_attacked(board, square, color.opposite()) {
             return false;
         }
 
-        traversed[0] = (traversed[0] as i32 + rank_dir) as usize;
-        traversed[1] = (traversed[1] as i32 + file_dir) as usize;
+        traversed = square.offset(rank_dir, file_dir);
     }
 
+    // Check if we have traversed past the edge of the board
+    if traversed.is_none() {
+        console_log!("[pieces::is_slide_clear]: ERROR! Tried to check a square off the edge of the board.");
+        return false;
+    }
 
     // Make sure the destination square doesn't put the king in check
-    if is_king && is_square_attacked(&board, dest, !is_white) {
+    if is_king && is_square_attacked(board, dest, color.opposite()) {
         return false;
     }
 
@@ -617,60 +650,47 @@ fn is_slide_clear_for_non_capture(board: &Board, src: [usize; 2], dest: [usize;
 
 /// Checks that the slide move is clear without checking the
 /// destination square. 
-fn is_slide_clear_for_capture(board: &Board, src: [usize; 2], dest: [usize; 2], is_white: bool) -> bool {
-    if src[0] == dest[0] && src[1] == dest[1] {
+fn is_slide_clear_for_capture(board: &Board, src: Square, dest: Square, color: Color) -> bool {
+    if src == dest {
         return true;
     }
     
-    let rank_dir = (dest[0] as i32 - src[0] as i32).signum();
-    let file_dir = (dest[1] as i32 - src[1] as i32).signum();
-    let new_dest = [(dest[0] as i32 - rank_dir) as usize,
-                              (dest[1] as i32 - file_dir) as usize];
+    let rank_dir = (dest.rank() as i32 - src.rank() as i32).signum();
+    let file_dir = (dest.file() as i32 - src.file() as i32).signum();
+    let new_dest = dest.offset(-rank_dir, -file_dir).unwrap();
 
-    return is_slide_clear_for_non_capture(&board, src, new_dest, is_white, false);
+    return is_slide_clear_for_non_capture(board, src, new_dest, color, false);
 }
 
 /// Returns true if a square is attacked by a piece of a specified colour.
-pub fn is_square_attacked(board : &Board, rank_file : [usize; 2], is_attacked_by_white : bool) -> bool {
-    let num_attackers = pieces_attacking_square(&board, rank_file, is_attacked_by_white).len();
-    return num_attackers > 0;
+pub fn is_square_attacked(board : &Board, square : Square, attacked_by : Color) -> bool {
+    return attackers_of_square(board, square, attacked_by, board.occupied()) != 0;
 }
 
-/// Returns the possible moves capable of attacking the target rank_file.
-pub fn pieces_attacking_square(board : &Board, rank_file : [usize; 2], is_attacked_by_white : bool) -> Vec<ChessMove> {
+/// Returns the squares of the pieces of the specified colour that attack the target square.
+/// Sliding attacks are blocked by the pieces in occupied, and only pieces in occupied can
+/// attack, so pieces can be removed to look through them.
+pub fn attackers_of_square(board : &Board, square : Square, attacked_by : Color, 
+    occupied : Bitboard) -> Bitboard {
+
+    let pieces = |kind: PieceKind| board.pieces(Piece::new(attacked_by, kind));
+
+    // A pawn attacks the square if a pawn of the other colour on the square would attack the pawn
+    let attackers = (bitboard::bishop_attacks(square, occupied) & (pieces(PieceKind::Bishop) | pieces(PieceKind::Queen))) |
+                    (bitboard::knight_attacks(square) & pieces(PieceKind::Knight)) |
+                    (bitboard::rook_attacks(square, occupied) & (pieces(PieceKind::Rook) | pieces(PieceKind::Queen))) |
+                    (bitboard::pawn_attacks(square, attacked_by.opposite()) & pieces(PieceKind::Pawn)) |
+                    (bitboard::king_attacks(square) & pieces(PieceKind::King));
+    return attackers & occupied;
+}
 
-    // Order of the pieces in the move_functions vector must match the order
-    // in the piece types vector.
-    // We do not need to check queen moves explicitly, because they will be checked
-    // by the bishop and rook moves.
-    let move_functions : Vec<&dyn Fn(&Board, [usize; 2], bool) -> Vec<ChessMove>> = 
-        vec![&bishop_moves, &knight_moves, &rook_moves, &pawn_capture_moves, 
-        &king_moves_move_into_check_allowed];
-    let piece_types = vec!['B', 'N', 'R', 'P', 'K'];
+/// Returns the possible moves capable of attacking the target square.
+pub fn pieces_attacking_square(board : &Board, square : Square, attacked_by : Color) -> Vec<ChessMove> {
+    let attackers = attackers_of_square(board, square, attacked_by, board.occupied());
 
     let mut attacking_squares : Vec<ChessMove> = vec![]; 
-    for i in 0..piece_types.len() {
-
-        let is_white = !is_attacked_by_white;
-        let piece_moves = move_functions[i](&board, rank_file, is_white);
-        for piece_move in piece_moves {
-            let attacking_square = piece_move.dest;
-
-            if !board.is_occupied(attacking_square) {
-                continue;
-            }
-
-            let piece_on_attacking_square = board.get_piece_on_square(attacking_square);
-            let piece_type = piece_on_attacking_square.to_ascii_uppercase();
-            if piece_type == piece_types[i] ||
-               (piece_type == 'Q' && (piece_types[i] == 'B' || piece_types[i] == 'R')) {
-                let mut attacking_move = piece_move;
-                attacking_move.dest = piece_move.src;
-                attacking_move.src = piece_move.dest;
-                attacking_move.piece = piece_on_attacking_square;
-                attacking_squares.push(attacking_move);
-            }
-        }
+    for attacking_square in bitboard::squares(attackers) {
+        attacking_squares.push(ChessMove::new(board, attacking_square, square));
     }
 
     return attacking_squares;
@@ -685,7 +705,9 @@ pub fn pieces_attacking_square(board : &Board, rank_file : [usize; 2], is_attack
 mod tests {
     use crate::console_log;
     use crate::board::Board;
-    use crate::pieces::{self};
+    use crate::pieces::{self, ChessMove, PackedMove, MoveType, SanError, UciError};
+    use crate::rules::legal_moves;
+    use crate::types::{sq, PieceKind, Color};
 
     #[test]
     fn is_slide_clear() {
@@ -693,127 +715,238 @@ mod tests {
         console_log!("top of is slide clear");
         let mut board = Board::new();
         console_log!("created board");
-        board.set_board_from_fen_string("8/3r4/8/3q1P2/8/8/6np/5k1Q ");
+        board.set_board_from_fen_string("8/3r4/8/3q1P2/8/8/6np/5k1Q ").unwrap();
         console_log!("set board");
-        let src = [5 as usize, 4 as usize];
-        let mut dest = [8 as usize, 4 as usize];
-        assert!( !pieces::is_slide_clear_for_non_capture(&board, src, dest, false, false) );
+        let src = sq("d5");
+        let mut dest = sq("d8");
+        assert!( !pieces::is_slide_clear_for_non_capture(&board, src, dest, Color::Black, false) );
 
-        dest = [5 as usize, 6 as usize];
-        assert!( pieces::is_slide_clear_for_capture(&board, src, dest, false) );
+        dest = sq("f5");
+        assert!( pieces::is_slide_clear_for_capture(&board, src, dest, Color::Black) );
     }
 
     #[test]
     fn possible_pawn_moves() {
         let mut board = Board::new();
-        board.set_board_from_fen_string("8/4p2p/4K3/8/2n5/1P6/6P1/8 ");
-        let mut src = [5 as usize, 4 as usize];
-        let mut is_white = true;
-        assert_eq!( pieces::pawn_moves(&board, src, is_white).len(), 2);
+        board.set_board_from_fen_string("8/4p2p/4K3/3P4/2n5/1P6/6P1/8 ").unwrap();
+        // No double move away from the starting rank
+        let mut src = sq("d5");
+        let mut color = Color::White;
+        assert_eq!( pieces::pawn_moves(&board, src, color).len(), 1);
 
-        src = [2 as usize, 7 as usize];
-        is_white = true;
-        assert_eq!( pieces::pawn_moves(&board, src, is_white).len(), 2);
+        src = sq("g2");
+        color = Color::White;
+        assert_eq!( pieces::pawn_moves(&board, src, color).len(), 2);
 
-        src = [7 as usize, 5 as usize];
-        is_white = false;
-        assert_eq!( pieces::pawn_moves(&board, src, is_white).len(), 0);
+        src = sq("e7");
+        color = Color::Black;
+        assert_eq!( pieces::pawn_moves(&board, src, color).len(), 0);
 
-        src = [7 as usize, 8 as usize];
-        is_white = false;
-        assert_eq!( pieces::pawn_moves(&board, src, is_white).len(), 2);
+        src = sq("h7");
+        color = Color::Black;
+        assert_eq!( pieces::pawn_moves(&board, src, color).len(), 2);
 
         // Test promotions
-        board.set_board_from_fen_string("7k/2P5/8/8/8/8/8/K7");
-        src = [7 as usize, 3 as usize];
-        is_white = true;
-        assert_eq!( pieces::pawn_moves(&board, src, is_white).len(), 4 );
-
-        board.set_board_from_fen_string("3q3k/2P5/8/8/8/8/8/K7");
-        src = [7 as usize, 3 as usize];
-        is_white = true;
-        assert_eq!( pieces::pawn_moves(&board, src, is_white).len(), 8 );
+        board.set_board_from_fen_string("7k/2P5/8/8/8/8/8/K7").unwrap();
+        src = sq("c7");
+        color = Color::White;
+        assert_eq!( pieces::pawn_moves(&board, src, color).len(), 4 );
+
+        board.set_board_from_fen_string("3q3k/2P5/8/8/8/8/8/K7").unwrap();
+        src = sq("c7");
+        color = Color::White;
+        assert_eq!( pieces::pawn_moves(&board, src, color).len(), 8 );
     }
 
     #[test]
     fn possible_knight_moves() {
         let mut board = Board::new();
-        board.set_board_from_fen_string("8/8/8/8/2N5/P7/1P1r2pp/7n");
-        let mut src = [4 as usize, 3 as usize];
-        let mut is_white = true;
-        assert_eq!( pieces::knight_moves(&board, src, is_white).len(), 6);
-
-        src = [1 as usize, 8 as usize];
-        is_white = true;
-        assert_eq!( pieces::knight_moves(&board, src, is_white).len(), 2);
+        board.set_board_from_fen_string("8/8/8/8/2N5/P7/1P1r2pp/7n").unwrap();
+        let mut src = sq("c4");
+        let mut color = Color::White;
+        assert_eq!( pieces::knight_moves(&board, src, color).len(), 6);
+
+        src = sq("h1");
+        color = Color::White;
+        assert_eq!( pieces::knight_moves(&board, src, color).len(), 2);
     }
 
     #[test]
     fn possible_queen_moves() {
         let mut board = Board::new();
-        board.set_board_from_fen_string("8/3r4/8/3q1P2/8/8/6np/5k1Q");
-        let mut src = [5 as usize, 4 as usize];
-        let mut is_white = false;
-        assert_eq!( pieces::queen_moves(&board, src, is_white).len(), 1+3+2+2+4+3+3+3);
-
-        src = [1 as usize, 8 as usize];
-        is_white = true;
-        assert_eq!( pieces::queen_moves(&board, src, is_white).len(), 3);
+        board.set_board_from_fen_string("8/3r4/8/3q1P2/8/8/6np/5k1Q").unwrap();
+        let mut src = sq("d5");
+        let mut color = Color::Black;
+        assert_eq!( pieces::queen_moves(&board, src, color).len(), 1+3+2+2+4+3+3+3);
+
+        src = sq("h1");
+        color = Color::White;
+        assert_eq!( pieces::queen_moves(&board, src, color).len(), 3);
     }
 
     #[test]
     fn possible_rook_moves() {
         let mut board = Board::new();
-        board.set_board_from_fen_string("8/3p4/8/3r1P2/8/8/6np/5Q1R");
-        let mut src = [5 as usize, 4 as usize];
-        let mut is_white = false;
-        assert_eq!( pieces::rook_moves(&board, src, is_white).len(), 1+2+3+4);
-
-        src = [1 as usize, 8 as usize];
-        is_white = true;
-        assert_eq!( pieces::rook_moves(&board, src, is_white).len(), 2);
+        board.set_board_from_fen_string("8/3p4/8/3r1P2/8/8/6np/5Q1R").unwrap();
+        let mut src = sq("d5");
+        let mut color = Color::Black;
+        assert_eq!( pieces::rook_moves(&board, src, color).len(), 1+2+3+4);
+
+        src = sq("h1");
+        color = Color::White;
+        assert_eq!( pieces::rook_moves(&board, src, color).len(), 2);
     }
 
     #[test]
     fn is_square_attacked() {
         let mut board = Board::new();
-        board.set_board_from_fen_string("8/3r4/8/3q1P2/8/8/6np/5k1Q");
-        let mut attacked_square = [3 as usize, 2 as usize];
-        let mut is_attacked_by_white = false;
-        assert!( pieces::is_square_attacked(&board, attacked_square, is_attacked_by_white) );
-        is_attacked_by_white = true;
-        assert!( !pieces::is_square_attacked(&board, attacked_square, is_attacked_by_white) );
+        board.set_board_from_fen_string("8/3r4/8/3q1P2/8/8/6np/5k1Q").unwrap();
+        let mut attacked_square = sq("b3");
+        let mut attacked_by = Color::Black;
+        assert!( pieces::is_square_attacked(&board, attacked_square, attacked_by) );
+        attacked_by = Color::White;
+        assert!( !pieces::is_square_attacked(&board, attacked_square, attacked_by) );
+
+        attacked_square = sq("h8");
+        attacked_by = Color::White;
+        assert!( !pieces::is_square_attacked(&board, attacked_square, attacked_by) );
+
+
+        board.set_board_from_fen_string("8/4p2p/4K3/8/2n5/1P6/6P1/8").unwrap();
+        attacked_square = sq("f6");
+        attacked_by = Color::White;
+        assert!( pieces::is_square_attacked(&board, attacked_square, attacked_by) );
+        attacked_by = Color::Black;
+        assert!( pieces::is_square_attacked(&board, attacked_square, attacked_by) );
+    }
+
+    #[test]
+    fn write_san() {
+        let board = Board::from_fen_string("r3k2r/1P1n4/8/3pP3/8/1N3N2/8/R3K2R w KQkq d6 0 1").unwrap();
+        let san = |src, dest| ChessMove::new(&board, src, dest).to_san(&board);
+        assert_eq!( san(sq("b3"), sq("d4")), "Nbd4" );
+        assert_eq!( san(sq("e5"), sq("d6")), "exd6" );
+        assert_eq!( san(sq("e1"), sq("g1")), "O-O" );
+        assert_eq!( san(sq("e1"), sq("c1")), "O-O-O" );
+        assert_eq!( san(sq("a1"), sq("a8")), "Rxa8+" );
+        assert_eq!( ChessMove::new_promotion(&board, sq("b7"), sq("a8"), PieceKind::Knight).to_san(&board), "bxa8=N" );
+
+        // Disambiguated by rank when the pieces are on the same file, and by both when neither is enough
+        let board = Board::from_fen_string("k7/8/8/8/1Q1Q4/8/1Q6/K7 w - - 0 1").unwrap();
+        assert_eq!( ChessMove::new(&board, sq("b2"), sq("c3")).to_san(&board), "Q2c3" );
+        assert_eq!( ChessMove::new(&board, sq("b4"), sq("c3")).to_san(&board), "Qb4c3" );
+        assert_eq!( ChessMove::new(&board, sq("d4"), sq("c3")).to_san(&board), "Qdc3" );
+
+        // Fool's mate
+        let board = Board::from_fen_string("rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq - 0 2").unwrap();
+        assert_eq!( ChessMove::new(&board, sq("d8"), sq("h4")).to_san(&board), "Qh4#" );
+    }
+
+    #[test]
+    fn write_san_for_game_moves() {
+        // Every move in the games should be written exactly as it is in the file
+        for game in include_str!("../static/resources/games.pgn").lines().take(100) {
+            let mut board = Board::new();
+            let moves : Vec<&str> = game.split_whitespace().collect();
+            for expected_san in &moves[..moves.len()-1] {
+                let matching_moves : Vec<ChessMove> = legal_moves(&board).into_iter()
+                    .filter(|chess_move| chess_move.to_san(&board) == *expected_san)
+                    .collect();
+                assert_eq!( matching_moves.len(), 1, "{} in {}", expected_san, board.to_fen() );
+                board.make_move(matching_moves[0]);
+            }
+        }
+    }
 
-        attacked_square = [8 as usize, 8 as usize];
-        is_attacked_by_white = true;
-        assert!( !pieces::is_square_attacked(&board, attacked_square, is_attacked_by_white) );
+    #[test]
+    fn read_san() {
+        let board = Board::from_fen_string("r3k2r/1P1n4/8/3pP3/8/1N3N2/8/R3K2R w KQkq d6 0 1").unwrap();
+        let read = |san| ChessMove::from_san(&board, san).map(|chess_move| (chess_move.src, chess_move.dest, chess_move.move_type));
+        assert_eq!( read("Nbd4"), Ok((sq("b3"), sq("d4"), MoveType::Standard)) );
+        assert_eq!( read("N3d4!?"), Err(SanError::AmbiguousMove(String::from("N3d4!?"))) );
+        assert_eq!( read("Nfd4"), Ok((sq("f3"), sq("d4"), MoveType::Standard)) );
+        assert_eq!( read("exd6 e.p."), Ok((sq("e5"), sq("d6"), MoveType::EnPassant)) );
+        assert_eq!( read("exd6"), Ok((sq("e5"), sq("d6"), MoveType::EnPassant)) );
+        assert_eq!( read("0-0"), Ok((sq("e1"), sq("g1"), MoveType::CastleKingSide)) );
+        assert_eq!( read("O-O-O"), Ok((sq("e1"), sq("c1"), MoveType::CastleQueenSide)) );
+        assert_eq!( read("Rxa8+"), Ok((sq("a1"), sq("a8"), MoveType::Standard)) );
+        assert_eq!( read("b8=Q"), Ok((sq("b7"), sq("b8"), MoveType::PromoteToQueen)) );
+        assert_eq!( read("bxa8N#"), Ok((sq("b7"), sq("a8"), MoveType::PromoteToKnight)) );
+
+        assert_eq!( read(""), Err(SanError::Empty) );
+        assert_eq!( read("Nd4"), Err(SanError::AmbiguousMove(String::from("Nd4"))) );
+        assert_eq!( read("b8"), Err(SanError::MissingPromotion(String::from("b8"))) );
+        assert_eq!( read("Ne5"), Err(SanError::IllegalMove(String::from("Ne5"))) );
+        assert_eq!( read("Ke9"), Err(SanError::InvalidSyntax(String::from("Ke9"))) );
+        assert_eq!( read("Ne4=Q"), Err(SanError::InvalidSyntax(String::from("Ne4=Q"))) );
+        assert_eq!( read("1/2-1/2"), Err(SanError::InvalidSyntax(String::from("1/2-1/2"))) );
+
+        // Every legal move can be read back from the notation it is written in
+        let board = Board::from_fen_string("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        for chess_move in legal_moves(&board) {
+            let read_move = ChessMove::from_san(&board, &chess_move.to_san(&board)).unwrap();
+            assert!( read_move.is_the_same_as(&chess_move) );
+        }
+    }
 
+    #[test]
+    fn read_and_write_uci() {
+        let board = Board::from_fen_string("r3k2r/1P1n4/8/3pP3/8/1N3N2/8/R3K2R w KQkq d6 0 1").unwrap();
+        let read = |uci| ChessMove::from_uci(&board, uci).map(|chess_move| (chess_move.src, chess_move.dest, chess_move.move_type));
+        assert_eq!( read("b3d4"), Ok((sq("b3"), sq("d4"), MoveType::Standard)) );
+        assert_eq!( read("e5d6"), Ok((sq("e5"), sq("d6"), MoveType::EnPassant)) );
+        assert_eq!( read("e1g1"), Ok((sq("e1"), sq("g1"), MoveType::CastleKingSide)) );
+        assert_eq!( read("e1h1"), Ok((sq("e1"), sq("g1"), MoveType::CastleKingSide)) );
+        assert_eq!( read("e1a1"), Ok((sq("e1"), sq("c1"), MoveType::CastleQueenSide)) );
+        assert_eq!( read("b7a8n"), Ok((sq("b7"), sq("a8"), MoveType::PromoteToKnight)) );
+
+        assert_eq!( read("b7b8"), Err(UciError::MissingPromotion(String::from("b7b8"))) );
+        assert_eq!( read("b3b4"), Err(UciError::IllegalMove(String::from("b3b4"))) );
+        assert_eq!( read("e2e4q"), Err(UciError::IllegalMove(String::from("e2e4q"))) );
+        assert_eq!( read("e2e9"), Err(UciError::InvalidSyntax(String::from("e2e9"))) );
+        assert_eq!( read("b7b8k"), Err(UciError::InvalidSyntax(String::from("b7b8k"))) );
+
+        for chess_move in legal_moves(&board) {
+            let read_move = ChessMove::from_uci(&board, &chess_move.to_uci()).unwrap();
+            assert!( read_move.is_the_same_as(&chess_move) );
+        }
+        assert_eq!( ChessMove::new_promotion(&board, sq("b7"), sq("b8"), PieceKind::Queen).to_uci(), "b7b8q" );
+    }
+
+    #[test]
+    fn pack_moves() {
+        assert_eq!( std::mem::size_of::<PackedMove>(), 2 );
+
+        let board = Board::from_fen_string("r3k2r/1P1n4/8/3pP3/8/1N3N2/8/R3K2R w KQkq d6 0 1").unwrap();
+        for chess_move in legal_moves(&board) {
+            let packed_move = PackedMove::from(chess_move);
+            assert_eq!( (packed_move.src(), packed_move.dest()), (chess_move.src, chess_move.dest) );
+            assert!( packed_move.to_chess_move(&board).unwrap().is_the_same_as(&chess_move) );
+        }
+        assert!( PackedMove::from(ChessMove::new(&board, sq("b3"), sq("c5"))).to_chess_move(&Board::new()).is_none() );
 
-        board.set_board_from_fen_string("8/4p2p/4K3/8/2n5/1P6/6P1/8");
-        attacked_square = [6 as usize, 6 as usize];
-        is_attacked_by_white = true;
-        assert!( pieces::is_square_attacked(&board, attacked_square, is_attacked_by_white) );
-        is_attacked_by_white = false;
-        assert!( pieces::is_square_attacked(&board, attacked_square, is_attacked_by_white) );
+        let promotion = ChessMove::new_promotion(&board, sq("b7"), sq("a8"), PieceKind::Rook);
+        assert_eq!( PackedMove::from(promotion).move_type(), MoveType::PromoteToRook );
+        assert_ne!( PackedMove::from(promotion), PackedMove::from(ChessMove::new_promotion(&board, sq("b7"), sq("a8"), PieceKind::Bishop)) );
     }
 
     #[test]
     fn possible_king_moves() {
         let mut board = Board::new();
-        board.set_board_from_fen_string("8/8/8/8/8/8/3r1PPP/R3K2R");
-        let src = [1 as usize, 5 as usize];
-        let is_white = true;
-        assert_eq!( pieces::king_moves(&board, src, is_white).len(), 3);
+        board.set_board_from_fen_string("8/8/8/8/8/8/3r1PPP/R3K2R").unwrap();
+        let src = sq("e1");
+        let color = Color::White;
+        assert_eq!( pieces::king_moves(&board, src, color).len(), 3);
 
-        board.set_board_from_fen_string("8/8/p7/P7/5k2/6q1/8/7K");
+        board.set_board_from_fen_string("8/8/p7/P7/5k2/6q1/8/7K").unwrap();
         board.render();
-        let src = [1 as usize, 8 as usize];
-        let is_white = true;
-        assert_eq!( pieces::king_moves(&board, src, is_white).len(), 0);
-
-        board.set_board_from_fen_string("2q2K2/8/8/1k6/8/8/8/8");
-        let src = [8 as usize, 6 as usize];
-        let is_white = true;
-        assert_eq!( pieces::king_moves(&board, src, is_white).len(), 3);        
+        let src = sq("h1");
+        let color = Color::White;
+        assert_eq!( pieces::king_moves(&board, src, color).len(), 0);
+
+        board.set_board_from_fen_string("2q2K2/8/8/1k6/8/8/8/8").unwrap();
+        let src = sq("f8");
+        let color = Color::White;
+        assert_eq!( pieces::king_moves(&board, src, color).len(), 3);        
     }
 }
\ No newline at end of file
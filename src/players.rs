@@ -3,8 +3,10 @@ use crate::utils::log;
 use crate::board::Board;
 use crate::pieces::ChessMove;
 use crate::engine;
-use crate::search::Node;
-use crate::book::read_opening_book;
+use crate::book::{read_opening_book, OpeningBook, BookSelection};
+use crate::polyglot::PolyglotBook;
+
+use std::rc::Rc;
 
 /// HumanPlayer: Moves are entered via the web UI.
 pub struct HumanPlayer {
@@ -20,25 +22,30 @@ impl HumanPlayer {
 
 /// ComputerPlayer: Moves are calculated by the chess engine.
 pub struct ComputerPlayer {
-    opening_book: Node,
+    opening_book: OpeningBook,
+    // Used before the built in opening book when one has been loaded
+    polyglot_book: Option<Rc<PolyglotBook>>,
+    book_selection: BookSelection,
 }
 
 impl ComputerPlayer {
     pub fn new() -> ComputerPlayer {
         console_log!("ComputerPlayer::new:");
-        let max_depth: usize = 10;
-        let mut maybe_book = read_opening_book(max_depth);
+        let maybe_book = read_opening_book();
         return match maybe_book {
             Some(book) => { 
                 console_log!("    Successfully read the opening book!");
                 ComputerPlayer {
                 opening_book: book,
+                polyglot_book: None,
+                book_selection: BookSelection::RandomWeighted,
             }},
             _=> { 
                 console_log!("    Failed to read the opening book!");
-                let board = Board::new();
                 ComputerPlayer {
-                opening_book: Node::new_root(&board),
+                opening_book: OpeningBook::new(),
+                polyglot_book: None,
+                book_selection: BookSelection::RandomWeighted,
             }}
         }
     }
@@ -49,25 +56,45 @@ impl ComputerPlayer {
 pub trait Player {
     fn make_move(&mut self, board: &Board) -> ChessMove;
     fn is_computer(&self) -> bool;
+    /// The name of the player, used in the tags of exported games.
+    fn name(&self) -> String;
+    /// Gives the player an opening book in the Polyglot format to play from.
+    fn set_polyglot_book(&mut self, book: Rc<PolyglotBook>);
+    /// Sets how the player chooses between the moves in the built in opening book.
+    fn set_book_selection(&mut self, selection: BookSelection);
 }
 
 impl Player for HumanPlayer {
     fn make_move(&mut self, _board: &Board) -> ChessMove {
         // Should never reach this. Human moves are entered by the web GUI.
-        assert!(false);
-        return ChessMove::new_empty_move();
+        unreachable!();
     }
 
     fn is_computer(&self) -> bool {
         return false;
     }
+
+    fn name(&self) -> String {
+        return String::from("Human");
+    }
+
+    fn set_polyglot_book(&mut self, _book: Rc<PolyglotBook>) {
+        // Human players don't use an opening book
+    }
+
+    fn set_book_selection(&mut self, _selection: BookSelection) {
+    }
 }
 
 impl Player for ComputerPlayer {
     fn make_move(&mut self, board: &Board) -> ChessMove {
         console_log!("players::ComputerPlayer::make_move: ");
 
-        if let Some(book_move) = engine::move_from_opening_book(&mut self.opening_book, &board) {
+        if let Some(book_move) = self.polyglot_book.as_ref().and_then(|book| book.weighted_move(board)) {
+            return book_move;
+        }
+
+        if let Some(book_move) = engine::move_from_opening_book(&self.opening_book, board, self.book_selection) {
             return book_move;
         }
 
@@ -78,4 +105,16 @@ impl Player for ComputerPlayer {
     fn is_computer(&self) -> bool {
         return true;
     }
+
+    fn name(&self) -> String {
+        return String::from("Athena Engine");
+    }
+
+    fn set_polyglot_book(&mut self, book: Rc<PolyglotBook>) {
+        self.polyglot_book = Some(book);
+    }
+
+    fn set_book_selection(&mut self, selection: BookSelection) {
+        self.book_selection = selection;
+    }
 }
\ No newline at end of file
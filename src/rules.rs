@@ -1,10 +1,15 @@
-use crate::console_log;
-use crate::utils::log;
 use crate::pieces::{self, ChessMove, MoveType};
 use crate::board::Board;
+use crate::bitboard::{self, Bitboard};
+use crate::types::{Square, Piece, PieceKind, Color};
 
 /// Checks if the requested move is legal based on the current position
 pub fn is_move_legal(board: &Board, requested_move: &ChessMove) -> bool {
+
+    // Only the side to move can move
+    if !board.is_occupied_by(requested_move.src, board.side_to_move()) {
+        return false;
+    }
     
     let possible_moves = possible_moves_from_square(board, requested_move.src);
 
@@ -23,79 +28,210 @@ pub fn is_move_legal(board: &Board, requested_move: &ChessMove) -> bool {
 /// position.
 pub fn all_possible_moves(board : &Board) -> Vec<ChessMove> {
 
-    if board.is_checkmate() || board.is_draw() {
+    if board.is_draw_other_than_stalemate() {
         // No possible moves if the game is over
         return vec![];
     }
 
-    let occupied_squares = board.all_occupied_squares(board.white_to_move());
+    return legal_moves(board);
+}
+
+/// Returns every legal move for the side to move, whether or not the game has
+/// already been drawn by repetition, the fifty move rule or insufficient material.
+pub fn legal_moves(board: &Board) -> Vec<ChessMove> {
+    let check_info = CheckInfo::new(board, board.side_to_move());
 
-    let mut all_possible_moves : Vec<ChessMove> = vec![];
-    for square in occupied_squares {
-        all_possible_moves.append(&mut possible_moves_from_square(&board, square));
+    let mut legal_moves : Vec<ChessMove> = vec![];
+    for square in board.all_occupied_squares(board.side_to_move()) {
+        legal_moves.append(&mut legal_moves_from_square(board, square, &check_info));
     }
 
-    return all_possible_moves;
+    return legal_moves;
 }
 
 /// possible_moves_from_square: Given a chess board and a square, 
 /// generates all possible chess moves for the piece on that square.
-pub fn possible_moves_from_square(board: &Board, rank_file: [usize; 2]) -> Vec<ChessMove> {
-
-    let piece = board.get_piece_on_square(rank_file);
-    let is_white = piece.is_uppercase();
-    let piece_type = piece.to_ascii_uppercase();
-
-    let moves: Vec<ChessMove>;
-    match piece_type {
-        'K' => moves = pieces::king_moves(&board, rank_file, is_white),
-        'Q' => moves = pieces::queen_moves(&board, rank_file, is_white),
-        'R' => moves = pieces::rook_moves(&board, rank_file, is_white),
-        'B' => moves = pieces::bishop_moves(&board, rank_file, is_white),
-        'N' => moves = pieces::knight_moves(&board, rank_file, is_white),
-        'P' => moves = pieces::pawn_moves(&board, rank_file, is_white),
-        _ => panic!(),
+pub fn possible_moves_from_square(board: &Board, square: Square) -> Vec<ChessMove> {
+    let color = match board.get_piece_on_square(square) {
+        Some(piece) => piece.color,
+        None => return vec![],
+    };
+    let check_info = CheckInfo::new(board, color);
+    return legal_moves_from_square(board, square, &check_info);
+}
+
+/// Generates the moves for the piece on the square, and removes the moves that
+/// leave the king in check.
+fn legal_moves_from_square(board: &Board, square: Square, check_info: &CheckInfo) -> Vec<ChessMove> {
+
+    let piece = board.get_piece_on_square(square).unwrap();
+    let color = piece.color;
+
+    // King moves never move into check, so they are already legal. In double
+    // check only the king can move.
+    if piece.kind == PieceKind::King {
+        return pieces::king_moves(board, square, color);
+    } else if check_info.checkers.count_ones() > 1 {
+        return vec![];
     }
 
-    // Cull any moves that end in the king in check
-    let mut moves_without_check : Vec<ChessMove> = vec![];
-    for chess_move in moves {
-        if chess_move.move_type == MoveType::Invalid {
-            console_log!("    move with piece {} is invalid", piece_type);
+    let moves = match piece.kind {
+        PieceKind::Queen => pieces::queen_moves(board, square, color),
+        PieceKind::Rook => pieces::rook_moves(board, square, color),
+        PieceKind::Bishop => pieces::bishop_moves(board, square, color),
+        PieceKind::Knight => pieces::knight_moves(board, square, color),
+        PieceKind::Pawn => pieces::pawn_moves(board, square, color),
+        PieceKind::King => unreachable!(),
+    };
+
+    return moves.into_iter()
+        .filter(|chess_move| check_info.is_legal(board, chess_move))
+        .collect();
+}
+
+/// CheckInfo. The pieces giving check and the pieces pinned to the king for one
+/// side, found once per position so each move can be checked for legality
+/// without making it.
+struct CheckInfo {
+    color: Color,
+    king: Option<Square>,
+    checkers: Bitboard,
+    // The squares a piece other than the king can move to. Every square when not
+    // in check, otherwise the checking piece and the squares between it and the king.
+    evasion_squares: Bitboard,
+    pinned: Bitboard,
+}
+
+impl CheckInfo {
+    fn new(board: &Board, color: Color) -> CheckInfo {
+        let mut check_info = CheckInfo {
+            color,
+            king: None,
+            checkers: 0,
+            evasion_squares: !0,
+            pinned: 0,
+        };
+
+        let king = match board.king_square(color) {
+            Some(king) => king,
+            None => return check_info,
+        };
+        check_info.king = Some(king);
+
+        let occupied = board.occupied();
+        check_info.checkers = pieces::attackers_of_square(board, king, color.opposite(), occupied);
+        if check_info.checkers.count_ones() == 1 {
+            let checker = Square::from_index(check_info.checkers.trailing_zeros() as usize);
+            check_info.evasion_squares = check_info.checkers | bitboard::between(king, checker);
+        } else if check_info.checkers != 0 {
+            check_info.evasion_squares = 0;
+        }
+
+        // A piece is pinned if it is the only piece between the king and an enemy
+        // slider that would otherwise attack the king.
+        let enemy = |kind: PieceKind| board.pieces(Piece::new(color.opposite(), kind));
+        let enemy_pieces = board.occupied_by(color.opposite());
+        let snipers = (bitboard::rook_attacks(king, enemy_pieces) & (enemy(PieceKind::Rook) | enemy(PieceKind::Queen))) |
+                      (bitboard::bishop_attacks(king, enemy_pieces) & (enemy(PieceKind::Bishop) | enemy(PieceKind::Queen)));
+        for sniper in bitboard::squares(snipers) {
+            let blockers = bitboard::between(king, sniper) & occupied;
+            if blockers.count_ones() == 1 && (blockers & board.occupied_by(color)) != 0 {
+                check_info.pinned |= blockers;
+            }
+        }
+
+        return check_info;
+    }
+
+    /// Checks a move by a piece other than the king does not leave the king in check.
+    fn is_legal(&self, board: &Board, chess_move: &ChessMove) -> bool {
+        let king = match self.king {
+            Some(king) => king,
+            None => return true,
+        };
+
+        if chess_move.move_type == MoveType::EnPassant {
+            return self.is_en_passant_legal(board, chess_move, king);
+        }
+
+        let dest = chess_move.dest.bit();
+        if (dest & self.evasion_squares) == 0 {
+            return false;
         }
-        let mut board_after_move = board.clone();
-        board_after_move.make_move(chess_move);
-        board_after_move.set_is_white_to_move(board.white_to_move());
-        if !board_after_move.is_check() {
-            moves_without_check.push(chess_move);
+
+        // A pinned piece can only move along the line between the king and the pinning piece
+        if (self.pinned & chess_move.src.bit()) != 0 {
+            return (bitboard::line(king, chess_move.src) & dest) != 0;
         }
+
+        return true;
     }
-    return moves_without_check;
-} 
+
+    /// En passant removes two pawns from the same rank at once, which can uncover an
+    /// attack on the king that neither pawn is pinned against on its own. The king is
+    /// checked directly with the pawns in their new positions instead.
+    fn is_en_passant_legal(&self, board: &Board, chess_move: &ChessMove, king: Square) -> bool {
+        let occupied = (board.occupied() & !chess_move.src.bit() & 
+                        !chess_move.captured_square().bit()) | chess_move.dest.bit();
+        return pieces::attackers_of_square(board, king, self.color.opposite(), occupied) == 0;
+    }
+}
 
 /// Tests to see that the rules are working
 #[cfg(test)]
 mod tests {
     use crate::pieces::ChessMove;
     use crate::board::Board;
-    use crate::rules::{is_move_legal, all_possible_moves, possible_moves_from_square};
+    use crate::types::sq;
+    use crate::rules::{is_move_legal, all_possible_moves, legal_moves, possible_moves_from_square};
 
     #[test]
     fn remove_king_from_check() {
         let mut board = Board::new();
-        board.set_board_from_fen_string("8/k7/8/3q1N2/8/8/2P1P3/3K4");
+        board.set_board_from_fen_string("8/k7/8/3q1N2/8/8/2P1P3/3K4").unwrap();
         
         assert_eq!(all_possible_moves(&board).len(), 3);
     }
 
+    #[test]
+    fn no_moves_with_insufficient_material() {
+        let mut board = Board::new();
+        board.set_board_from_fen_string("8/8/4k3/8/8/4K3/8/6N1").unwrap();
+        assert_eq!(all_possible_moves(&board).len(), 0);
+    }
+
     #[test]
     fn move_piece_pinned_to_king() {
         let mut board = Board::new();
-        board.set_board_from_fen_string("rnbqk1nr/pppp1ppp/4p3/8/1b1P4/2N5/PPP1PPPP/R1BQKBNR");
-        let illegal_move = ChessMove::new(&board, [3, 3], [5, 2]);
-        let legal_move = ChessMove::new(&board, [1, 3], [2, 4]);
+        board.set_board_from_fen_string("rnbqk1nr/pppp1ppp/4p3/8/1b1P4/2N5/PPP1PPPP/R1BQKBNR").unwrap();
+        let illegal_move = ChessMove::new(&board, sq("c3"), sq("b5"));
+        let legal_move = ChessMove::new(&board, sq("c1"), sq("d2"));
 
         assert!( !is_move_legal(&board, &illegal_move) );
         assert!( is_move_legal(&board, &legal_move) )
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn only_evasions_when_in_check() {
+        let mut board = Board::new();
+        board.set_board_from_fen_string("4r2k/8/8/8/R7/8/8/4K3 w - - 0 1").unwrap();
+        // Block the check with the rook, or move the king off the e file
+        assert_eq!( legal_moves(&board).len(), 5 );
+
+        // Double check, only the king can move
+        board.set_board_from_fen_string("4r2k/8/8/8/R7/3n4/8/4K3 w - - 0 1").unwrap();
+        assert_eq!( legal_moves(&board).len(), 3 );
+    }
+
+    #[test]
+    fn en_passant_pinned_along_rank() {
+        let mut board = Board::new();
+        board.set_board_from_fen_string("8/8/8/KPp4r/8/8/8/7k w - c6 0 1").unwrap();
+        let en_passant = ChessMove::new(&board, sq("b5"), sq("c6"));
+        assert!( !is_move_legal(&board, &en_passant) );
+
+        board.set_board_from_fen_string("8/8/8/KPp5/8/8/8/7k w - c6 0 1").unwrap();
+        let en_passant = ChessMove::new(&board, sq("b5"), sq("c6"));
+        assert!( is_move_legal(&board, &en_passant) );
+    }
+}
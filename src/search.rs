@@ -1,17 +1,18 @@
 use crate::board::Board;
 use crate::evaluate::{evaluate, CHECKMATE_VAL};
 use crate::rules::all_possible_moves;
-use crate::pieces::ChessMove;
+use crate::pieces::{ChessMove, PackedMove, MoveType};
+use crate::see::static_exchange_evaluation;
 use crate::console_log;
 use crate::utils::log;
 
 /// Returns the move that leads toward the position with the best evaluation
 /// for the selected colour. 
-pub fn find_best_move(root : &Node) -> ChessMove {
+pub fn find_best_move(root : &Node) -> PackedMove {
 
-    assert!(root.children.len() > 0);
+    assert!(!root.children.is_empty());
 
-    let is_white = root.position.white_to_move();
+    let is_white = root.is_white_to_move;
 
     let mut best_eval_inx : usize = 0;
     let mut best_minimax_eval : f32;
@@ -22,10 +23,6 @@ pub fn find_best_move(root : &Node) -> ChessMove {
     }
 
     let num_possible_moves = root.children.len();
-    if num_possible_moves ==  0 {
-        console_log!("search::find_best_move: No legal moves.");
-        return ChessMove::new_empty_move();
-    }
 
     println!("find_best_move:");
     println!("    num possible moves = {}", num_possible_moves);
@@ -41,47 +38,52 @@ pub fn find_best_move(root : &Node) -> ChessMove {
     }
 
     console_log!("  best_eval_inx = {}", best_eval_inx);
-    return root.children[best_eval_inx].chess_move_from_parent.clone();
+    return root.children[best_eval_inx].chess_move_from_parent.unwrap();
 }
 
 /// Create search tree of possible positions to a given depth.
 /// Uses a minimax algorithm with alpha-beta trimming for improved efficiency.
+/// Moves are made and taken back on the board, so the board is unchanged once
+/// the search returns.
 /// https://en.wikipedia.org/wiki/Minimax
-pub fn alpha_beta_minimax(mut node: &mut Node, depth: usize, 
+pub fn alpha_beta_minimax(node: &mut Node, board: &mut Board, depth: usize, 
                           initial_alpha: f32, initial_beta: f32, maximizing_player: bool) -> f32 {
 
     if depth == 0 {
-        node.eval = evaluate(&node.position);
+        node.eval = evaluate(board);
         return node.eval;
     }
 
-    let all_possible_moves = all_possible_moves(&node.position);
+    let all_possible_moves = order_moves(board, all_possible_moves(board));
     let num_possible_moves = all_possible_moves.len();
     if num_possible_moves == 0 {
-        node.eval = evaluate(&node.position);
+        node.eval = evaluate(board);
         return node.eval;
     }
 
-    for i in 0..num_possible_moves {
-        let mut child = node.position.clone();
-        child.make_move(all_possible_moves[i]);
-        node.children.push(Node::new(&child, &all_possible_moves[i]));
-    }
-
     let mut alpha = initial_alpha;
     let mut beta = initial_beta;
 
     let mut minimax_eval = if maximizing_player { -CHECKMATE_VAL } else { CHECKMATE_VAL };
 
-    for i in 0..num_possible_moves {
+    for (chess_move, exchange_value) in all_possible_moves {
 
         // Make the move
-        let mut next_position = node.position.clone();
-        next_position.make_move(all_possible_moves[i]);
-        node.children.push(Node::new(&next_position, &all_possible_moves[i]));
+        let undo = board.make_move(chess_move);
+
+        // The evaluation after the last move can't see the recapture, so a capture that
+        // loses material would look like it wins it. These are skipped, unless they give
+        // check or there is nothing else to search.
+        if depth == 1 && exchange_value < 0.0 && !node.children.is_empty() && !board.is_check() {
+            board.unmake_move(chess_move, undo);
+            continue;
+        }
+        node.children.push(Node::new(board, &chess_move));
 
         // Evaluate the position
-        let eval = alpha_beta_minimax(&mut node.children[i], depth-1, alpha, beta, !maximizing_player);
+        let child = node.children.last_mut().unwrap();
+        let eval = alpha_beta_minimax(child, board, depth-1, alpha, beta, !maximizing_player);
+        board.unmake_move(chess_move, undo);
 
         if maximizing_player {
             minimax_eval = max(minimax_eval, eval);
@@ -99,45 +101,31 @@ pub fn alpha_beta_minimax(mut node: &mut Node, depth: usize,
     return minimax_eval;
 }
 
-/// Performance test move path optimization 
-/// https://www.chessprogramming.org/Perft
-pub fn perft(board: &Board, depth: u32) -> u32 {
-
-    if depth == 0 {
-        return 1;
-    }
-
-    let mut total_leaves = 0;
-    let all_possible_moves = all_possible_moves(&board);
-    let num_possible_moves = all_possible_moves.len();
-
-    for i in 0..num_possible_moves {
-        let mut next_position = board.clone();
-        next_position.make_move(all_possible_moves[i]);
-        total_leaves += perft(&next_position, depth-1);
-    }
-
-    return total_leaves;
-}
-
-pub fn count_leaves_in_tree(root : &Node, num_leaves : &mut u32, num_checks : &mut u32) {
-
-    let num_child_nodes = root.children.len();
-
-    if root.position.is_check() {
-        *num_checks = *num_checks + 1;
-    }
-
-    // Break condition, when there are no child nodes we are
-    // at a leaf.
-    if num_child_nodes == 0 {
-        *num_leaves = *num_leaves + 1 as u32;
-        return;
-    }
-
-    for i in 0..num_child_nodes {
-        count_leaves_in_tree(&root.children[i], num_leaves, num_checks);
-    }
+/// Orders the moves so the best captures are searched first, which lets alpha-beta
+/// prune more of the tree. Captures that win or trade material come first, with the
+/// most material won first, then the quiet moves, then captures that lose material.
+/// Each move is returned with its static exchange evaluation, or zero for quiet moves.
+fn order_moves(board: &Board, moves: Vec<ChessMove>) -> Vec<(ChessMove, f32)> {
+    let is_capture = |chess_move: &ChessMove| board.is_occupied(chess_move.dest) || chess_move.move_type == MoveType::EnPassant;
+    let mut ordered_moves : Vec<(ChessMove, f32)> = moves.into_iter().map(|chess_move| {
+        let exchange_value = if is_capture(&chess_move) { static_exchange_evaluation(board, &chess_move) } else { 0.0 };
+        (chess_move, exchange_value)
+    }).collect();
+
+    let order = |(chess_move, exchange_value): &(ChessMove, f32)| {
+        if !is_capture(chess_move) {
+            return 1;
+        } else if *exchange_value >= 0.0 {
+            return 0;
+        }
+        return 2;
+    };
+
+    // Stable sort, so moves that are equally good stay in the order they were generated
+    ordered_moves.sort_by(|a, b| {
+        order(a).cmp(&order(b)).then(b.1.partial_cmp(&a.1).unwrap())
+    });
+    return ordered_moves;
 }
 
 fn max(a : f32, b : f32) -> f32 {
@@ -154,80 +142,35 @@ fn min(a : f32, b : f32) -> f32 {
     return a;
 }
 
-/// Each node in the search tree stores the position and
-/// the leaves of the tree will have an evaluation.
+/// The leaves of the search tree will have an evaluation. The root has no
+/// move from its parent.
 pub struct Node {
     pub children: Vec<Node>,
-    pub chess_move_from_parent: ChessMove,
-    pub position: Board,
+    pub chess_move_from_parent: Option<PackedMove>,
+    pub is_white_to_move: bool,
     pub is_evaluated: bool, 
     pub eval: f32,
 }
 
 impl Node {
+    /// Creates a node for the position on the board, after the chess move has been made.
     pub fn new(board : &Board, chess_move : &ChessMove) -> Node {
         return Node {
             children : vec![],
-            chess_move_from_parent : chess_move.clone(),
-            position : board.clone(),
+            chess_move_from_parent : Some(PackedMove::from(*chess_move)),
+            is_white_to_move : board.white_to_move(),
             is_evaluated : false,
             eval : 0.0,
         }
     }
 
     pub fn new_root(board : &Board) -> Node {
-        let empty_move = ChessMove::new_empty_move();
-        return Node::new(&board, &empty_move);
+        return Node {
+            children : vec![],
+            chess_move_from_parent : None,
+            is_white_to_move : board.white_to_move(),
+            is_evaluated : false,
+            eval : 0.0,
+        }
     }
 }
-
-#[cfg(test)]
-mod tests {
-    use crate::console_log;
-    use crate::board::Board;
-    use crate::pieces::ChessMove;
-    use crate::search::{alpha_beta_minimax, perft, Node};
-    use crate::evaluate::CHECKMATE_VAL;
-
-    // Testing the search and chess engine using known perf results:
-    // https://www.chessprogramming.org/Perft_Results 
-    // These tests are broken, since alpha beta pruning 
-    #[test]
-    fn perft_test_start_pos() {
-
-        let mut board = Board::new();
-        let mut depth = 0;
-        let mut num_leaves = perft(&board, depth);
-        assert_eq!( num_leaves, 1 );
-
-        depth = 1;
-        num_leaves = perft(&board, depth);
-        assert_eq!(num_leaves, 20);
-
-        depth = 2;
-        num_leaves = perft(&board, depth);
-        assert_eq!(num_leaves, 400);
-
-        // depth = 3;
-        // num_leaves = perft(&board, depth);
-        // assert_eq!(num_leaves, 8902); // failing, getting 9194 positions
-
-        // depth = 4;
-        // num_leaves = perft(&board, depth);
-        // assert_eq!(num_leaves, 197281); // failing, getting 209691 positions
-    }
-
-    #[test]
-    fn perft_test_kiwipete() {
-        
-        let mut board = Board::new();
-        board.set_board_from_fen_string("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R");
-        let mut depth = 1;
-        let mut num_leaves = perft(&board, depth);
-        assert_eq!( num_leaves, 48);
-       
-        // depth = 2;
-        // num_leaves = perft(&board, depth);
-        // assert_eq!(num_leaves, 2039); // Fails, gets 2082 positions...
-    }
-}
\ No newline at end of file
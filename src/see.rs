@@ -0,0 +1,157 @@
+use crate::board::Board;
+use crate::bitboard::Bitboard;
+use crate::pieces::{ChessMove, attackers_of_square, pieces_attacking_square};
+use crate::types::{Square, Piece, PieceKind, Color};
+use crate::evaluate::{CHECKMATE_VAL, QUEEN_VAL, ROOK_VAL, BISHOP_VAL, KNIGHT_VAL, PAWN_VAL};
+
+/// Static exchange evaluation. Returns the material won by the side making the move,
+/// once both sides have finished capturing on the destination square. Each side
+/// recaptures with its least valuable piece, and stops as soon as continuing would
+/// lose material. Sliders behind the capturing pieces join in as they are uncovered.
+/// Pins are ignored. For a move that is not a capture, the result is zero, or negative
+/// if the piece can be won on its new square.
+/// https://www.chessprogramming.org/Static_Exchange_Evaluation
+pub fn static_exchange_evaluation(board: &Board, chess_move: &ChessMove) -> f32 {
+    let captured_square = chess_move.captured_square();
+
+    // A promotion also wins the difference between the new piece and the pawn
+    let (piece_on_dest, promotion_gain) = match chess_move.move_type.promotion_kind() {
+        Some(kind) => {
+            let promoted_piece = Piece::new(chess_move.piece.color, kind);
+            (promoted_piece, piece_value(promoted_piece) - PAWN_VAL)
+        },
+        None => (chess_move.piece, 0.0),
+    };
+
+    let captured_value = board.get_piece_on_square(captured_square).map_or(0.0, piece_value) + promotion_gain;
+    let occupied = board.occupied() & !captured_square.bit();
+    return exchange(board, chess_move.dest, chess_move.src, piece_on_dest, captured_value, occupied);
+}
+
+/// Returns the squares of the pieces of the specified colour that the other side can
+/// win material from by capturing, with the best capture judged by static exchange
+/// evaluation. The king is never included.
+pub fn hanging_pieces(board: &Board, color: Color) -> Vec<Square> {
+    let mut hanging : Vec<Square> = vec![];
+    for square in board.all_occupied_squares(color) {
+        if board.is_occupied_by_king(square) {
+            continue;
+        }
+
+        let captures = pieces_attacking_square(board, square, color.opposite());
+        if captures.iter().any(|capture| static_exchange_evaluation(board, capture) > 0.0) {
+            hanging.push(square);
+        }
+    }
+    return hanging;
+}
+
+/// Plays out the captures on the target square after the first piece has moved there.
+fn exchange(board: &Board, target: Square, first_src: Square, first_piece: Piece,
+    captured_value: f32, initial_occupied: Bitboard) -> f32 {
+
+    // gains[i] is the material won by the side making capture i, if the exchange stops after it
+    let mut gains : Vec<f32> = vec![captured_value];
+    let mut occupied = initial_occupied;
+    let mut src = first_src;
+    let mut piece_on_target = first_piece;
+    let mut color_to_capture = first_piece.color.opposite();
+
+    loop {
+        // The piece that just captured leaves its square, which can uncover an X-ray attacker
+        occupied &= !src.bit();
+
+        let attackers = attackers_of_square(board, target, color_to_capture, occupied);
+        let (attacker_square, attacker) = match least_valuable_attacker(board, attackers, color_to_capture) {
+            Some(least_valuable) => least_valuable,
+            None => break,
+        };
+
+        let previous_gain = gains[gains.len() - 1];
+        gains.push(piece_value(piece_on_target) - previous_gain);
+
+        src = attacker_square;
+        piece_on_target = attacker;
+        color_to_capture = color_to_capture.opposite();
+    }
+
+    // Work back from the end of the exchange. Each side only makes its capture if
+    // that is better than stopping the exchange before it.
+    for i in (1..gains.len()).rev() {
+        gains[i-1] = -f32::max(-gains[i-1], gains[i]);
+    }
+    return gains[0];
+}
+
+fn least_valuable_attacker(board: &Board, attackers: Bitboard, color: Color) -> Option<(Square, Piece)> {
+    for &kind in PieceKind::ALL.iter() {
+        let piece = Piece::new(color, kind);
+        let attackers_of_type = attackers & board.pieces(piece);
+        if attackers_of_type != 0 {
+            let square = Square::from_index(attackers_of_type.trailing_zeros() as usize);
+            return Some((square, piece));
+        }
+    }
+    return None;
+}
+
+/// The material value of a piece. Capturing the king ends the exchange, so it is
+/// worth more than everything else on the board.
+fn piece_value(piece: Piece) -> f32 {
+    return match piece.kind {
+        PieceKind::Pawn => PAWN_VAL,
+        PieceKind::Knight => KNIGHT_VAL,
+        PieceKind::Bishop => BISHOP_VAL,
+        PieceKind::Rook => ROOK_VAL,
+        PieceKind::Queen => QUEEN_VAL,
+        PieceKind::King => CHECKMATE_VAL,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::board::Board;
+    use crate::pieces::ChessMove;
+    use crate::see::{static_exchange_evaluation, hanging_pieces};
+    use crate::types::{sq, Color};
+
+    #[test]
+    fn exchange_on_defended_square() {
+        // Rook takes an undefended pawn
+        let mut board = Board::new();
+        board.set_board_from_fen_string("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1").unwrap();
+        let capture = ChessMove::new(&board, sq("e1"), sq("e5"));
+        assert_eq!( static_exchange_evaluation(&board, &capture), 1.0 );
+
+        // Knight takes a pawn defended by a pawn, and the knight is lost
+        board.set_board_from_fen_string("1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1").unwrap();
+        let capture = ChessMove::new(&board, sq("d3"), sq("e5"));
+        assert_eq!( static_exchange_evaluation(&board, &capture), 1.0 - 3.0 );
+    }
+
+    #[test]
+    fn exchange_with_x_ray_attackers() {
+        // The rooks doubled on the e file win the pawn, even though only one attacks it directly
+        let mut board = Board::new();
+        board.set_board_from_fen_string("4r1k1/8/8/4p3/8/8/4R3/4R1K1 w - - 0 1").unwrap();
+        let capture = ChessMove::new(&board, sq("e2"), sq("e5"));
+        assert_eq!( static_exchange_evaluation(&board, &capture), 1.0 );
+
+        // Without the second rook, white loses the rook for a pawn
+        board.set_board_from_fen_string("4r1k1/8/8/4p3/8/8/4R3/6K1 w - - 0 1").unwrap();
+        let capture = ChessMove::new(&board, sq("e2"), sq("e5"));
+        assert_eq!( static_exchange_evaluation(&board, &capture), 1.0 - 5.0 );
+    }
+
+    #[test]
+    fn find_hanging_pieces() {
+        let mut board = Board::new();
+        board.set_board_from_fen_string("4k3/8/3p4/4n3/8/2B5/8/4K3 w - - 0 1").unwrap();
+        // The knight is defended by a pawn, so the bishop can't win it
+        assert!( hanging_pieces(&board, Color::Black).is_empty() );
+
+        board.set_board_from_fen_string("4k3/8/8/4n3/8/2B5/8/4K3 w - - 0 1").unwrap();
+        assert_eq!( hanging_pieces(&board, Color::Black), vec![sq("e5")] );
+        assert!( hanging_pieces(&board, Color::White).is_empty() );
+    }
+}
@@ -0,0 +1,248 @@
+use crate::bitboard::Bitboard;
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Square. One of the 64 squares of the board, stored as its index from a1 = 0,
+/// b1 = 1 ... h8 = 63, the same order as the bits of a bitboard. Ranks and files
+/// start at 1 in the bottom left square (a1).
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Square(u8);
+
+impl Square {
+    /// Returns None if the rank or file is off the board.
+    pub fn new(rank: usize, file: usize) -> Option<Square> {
+        if !(1..=8).contains(&rank) || !(1..=8).contains(&file) {
+            return None;
+        }
+        return Some(Square(((rank - 1)*8 + (file - 1)) as u8));
+    }
+
+    /// Returns the square with the bit index, which must be less than 64.
+    pub fn from_index(square_inx: usize) -> Square {
+        assert!(square_inx < 64);
+        return Square(square_inx as u8);
+    }
+
+    pub fn index(self) -> usize {
+        return self.0 as usize;
+    }
+
+    pub fn rank(self) -> usize {
+        return self.index()/8 + 1;
+    }
+
+    pub fn file(self) -> usize {
+        return self.index()%8 + 1;
+    }
+
+    /// Returns a bitboard with only this square set.
+    pub fn bit(self) -> Bitboard {
+        return 1 << self.0;
+    }
+
+    /// Returns the square the given number of ranks and files away, or None if it is off the board.
+    pub fn offset(self, delta_rank: i32, delta_file: i32) -> Option<Square> {
+        let rank = self.rank() as i32 + delta_rank;
+        let file = self.file() as i32 + delta_file;
+        if rank < 1 || file < 1 {
+            return None;
+        }
+        return Square::new(rank as usize, file as usize);
+    }
+}
+
+/// Reads a chess coordinate such as e4 or f3.
+impl FromStr for Square {
+    type Err = ParseSquareError;
+
+    fn from_str(coord: &str) -> Result<Square, ParseSquareError> {
+        let bytes = coord.as_bytes();
+        if bytes.len() != 2 || !(b'a'..=b'h').contains(&bytes[0]) || !(b'1'..=b'8').contains(&bytes[1]) {
+            return Err(ParseSquareError(coord.to_string()));
+        }
+        let file = (bytes[0] - b'a' + 1) as usize;
+        let rank = (bytes[1] - b'0') as usize;
+        return Ok(Square::new(rank, file).unwrap());
+    }
+}
+
+impl fmt::Display for Square {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let file_alpha = (b'a' + self.file() as u8 - 1) as char;
+        return write!(f, "{}{}", file_alpha, self.rank());
+    }
+}
+
+/// The string could not be read as a square.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseSquareError(pub String);
+
+impl fmt::Display for ParseSquareError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "invalid square '{}', expected a coordinate such as e4", self.0);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Color {
+    White,
+    Black,
+}
+
+impl Color {
+    pub fn from_is_white(is_white: bool) -> Color {
+        return if is_white { Color::White } else { Color::Black };
+    }
+
+    pub fn is_white(self) -> bool {
+        return self == Color::White;
+    }
+
+    pub fn opposite(self) -> Color {
+        return match self {
+            Color::White => Color::Black,
+            Color::Black => Color::White,
+        };
+    }
+
+    /// 0 for white and 1 for black, used to index per colour tables.
+    pub fn index(self) -> usize {
+        return self as usize;
+    }
+
+    /// The rank the pieces of this colour start on, and pawns of the other colour promote on.
+    pub fn back_rank(self) -> usize {
+        return if self.is_white() { 1 } else { 8 };
+    }
+
+    /// The direction this colour's pawns move in, as a change in rank.
+    pub fn pawn_direction(self) -> i32 {
+        return if self.is_white() { 1 } else { -1 };
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum PieceKind {
+    Pawn,
+    Knight,
+    Bishop,
+    Rook,
+    Queen,
+    King,
+}
+
+impl PieceKind {
+    pub const ALL: [PieceKind; 6] = [PieceKind::Pawn, PieceKind::Knight, PieceKind::Bishop,
+                                     PieceKind::Rook, PieceKind::Queen, PieceKind::King];
+
+    /// The kinds a pawn can promote to, the most valuable first.
+    pub const PROMOTIONS: [PieceKind; 4] = [PieceKind::Queen, PieceKind::Rook, PieceKind::Bishop, PieceKind::Knight];
+
+    /// Reads the letter of a piece, in either case, such as N or n for a knight.
+    pub fn from_char(ch: char) -> Option<PieceKind> {
+        return match ch.to_ascii_uppercase() {
+            'P' => Some(PieceKind::Pawn),
+            'N' => Some(PieceKind::Knight),
+            'B' => Some(PieceKind::Bishop),
+            'R' => Some(PieceKind::Rook),
+            'Q' => Some(PieceKind::Queen),
+            'K' => Some(PieceKind::King),
+            _ => None,
+        };
+    }
+
+    /// The upper case letter of the piece.
+    pub fn to_char(self) -> char {
+        return match self {
+            PieceKind::Pawn => 'P',
+            PieceKind::Knight => 'N',
+            PieceKind::Bishop => 'B',
+            PieceKind::Rook => 'R',
+            PieceKind::Queen => 'Q',
+            PieceKind::King => 'K',
+        };
+    }
+
+    /// The index of the kind in the order PNBRQK.
+    pub fn index(self) -> usize {
+        return self as usize;
+    }
+}
+
+/// Piece. A piece of one colour, written as in a fen string, upper case for
+/// white and lower case for black.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Piece {
+    pub color: Color,
+    pub kind: PieceKind,
+}
+
+impl Piece {
+    pub fn new(color: Color, kind: PieceKind) -> Piece {
+        return Piece { color, kind };
+    }
+
+    /// Reads a piece letter such as N for a white knight or q for a black queen.
+    pub fn from_char(ch: char) -> Option<Piece> {
+        let kind = PieceKind::from_char(ch)?;
+        let color = Color::from_is_white(ch.is_ascii_uppercase());
+        return Some(Piece::new(color, kind));
+    }
+
+    pub fn to_char(self) -> char {
+        let ch = self.kind.to_char();
+        return if self.is_white() { ch } else { ch.to_ascii_lowercase() };
+    }
+
+    pub fn is_white(self) -> bool {
+        return self.color.is_white();
+    }
+
+    /// The index of the piece in the order PNBRQKpnbrqk, used to index per piece tables.
+    pub fn index(self) -> usize {
+        return self.color.index()*6 + self.kind.index();
+    }
+}
+
+impl fmt::Display for Piece {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}", self.to_char());
+    }
+}
+
+/// Reads a square in tests, such as sq("e4").
+#[cfg(test)]
+pub fn sq(coord: &str) -> Square {
+    return coord.parse().unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::{Square, Piece, PieceKind, Color, ParseSquareError};
+
+    #[test]
+    fn read_and_write_squares() {
+        let square: Square = "e4".parse().unwrap();
+        assert_eq!( (square.rank(), square.file()), (4, 5) );
+        assert_eq!( square.to_string(), "e4" );
+        assert_eq!( Square::new(1, 1).unwrap().index(), 0 );
+        assert_eq!( Square::new(8, 8).unwrap().to_string(), "h8" );
+        assert_eq!( Square::new(9, 1), None );
+        assert_eq!( square.offset(4, -4).map(|square| square.to_string()), Some(String::from("a8")) );
+        assert_eq!( square.offset(5, 0), None );
+        assert_eq!( "e9".parse::<Square>(), Err(ParseSquareError(String::from("e9"))) );
+        assert_eq!( "e".parse::<Square>(), Err(ParseSquareError(String::from("e"))) );
+    }
+
+    #[test]
+    fn read_and_write_pieces() {
+        let knight = Piece::from_char('n').unwrap();
+        assert_eq!( knight, Piece::new(Color::Black, PieceKind::Knight) );
+        assert_eq!( knight.to_char(), 'n' );
+        assert_eq!( knight.index(), 7 );
+        assert_eq!( Piece::from_char('K').unwrap().index(), 5 );
+        assert_eq!( Piece::from_char('x'), None );
+        assert_eq!( Color::White.opposite(), Color::Black );
+    }
+}
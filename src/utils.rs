@@ -1,5 +1,8 @@
 use wasm_bindgen::prelude::*;
 
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{SystemTime, UNIX_EPOCH};
+
 #[wasm_bindgen]
 extern "C" {
     #[wasm_bindgen(js_namespace = console)]
@@ -18,16 +21,15 @@ macro_rules! console_log {
     ($($t:tt)*) => (eprintln!("{}", &format_args!($($t)*).to_string()));
 }
 
-/// Converts a chess coordinate (such as e4, f3) to a rank and file
-/// Ranks and files start at 1 in the bottom left square (a1)
-pub fn coord_to_rank_file(coord : &str) -> [usize; 2] {
-    assert!(coord.len() == 2);
-
-    let mut chs = coord.bytes();
-    let file_alpha = chs.next().unwrap();
-    let rank_digit = chs.next().unwrap();
-    let rank = (rank_digit - b'0') as usize;
-    let file = (file_alpha - b'a' + 1) as usize;
+/// The current time in milliseconds, used to limit how long the engine searches for.
+#[cfg(target_arch = "wasm32")]
+pub fn now_ms() -> f64 {
+    return js_sys::Date::now();
+}
 
-    return [rank, file];
-}
\ No newline at end of file
+/// The current time in milliseconds. std::time is not available in wasm, so this
+/// is only used when running the tests.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn now_ms() -> f64 {
+    return SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs_f64()*1000.0;
+}
@@ -0,0 +1,123 @@
+use crate::types::{Square, Piece};
+
+/// Zobrist hashing. Each piece on each square, the side to move, each castle right
+/// and each en passant file is given a random 64 bit key. The key of a position is
+/// all of its keys xor'd together, so it can be updated incrementally as moves are made.
+/// https://www.chessprogramming.org/Zobrist_Hashing
+struct ZobristKeys {
+    pieces: [[u64; 64]; 12],
+    black_to_move: u64,
+    castle_rights: [u64; 4],
+    en_passant_files: [u64; 8],
+}
+
+/// The keys are generated at compile time from a fixed seed, so the key of a
+/// position is the same every time the engine runs.
+static ZOBRIST_KEYS: ZobristKeys = generate_keys(0x0a7e_4a00_c4e5_5b0a);
+
+/// Castle rights, used to index the castle right keys.
+pub const WHITE_KING_SIDE: usize = 0;
+pub const WHITE_QUEEN_SIDE: usize = 1;
+pub const BLACK_KING_SIDE: usize = 2;
+pub const BLACK_QUEEN_SIDE: usize = 3;
+
+/// Returns the key for a piece on a square. Squares are indexed in the same order
+/// as a bitboard, a1, b1, c1 ... f8, g8, h8.
+pub fn piece_key(piece: Piece, square: Square) -> u64 {
+    return ZOBRIST_KEYS.pieces[piece.index()][square.index()];
+}
+
+/// Included in the key when it is black's turn to move.
+pub fn black_to_move_key() -> u64 {
+    return ZOBRIST_KEYS.black_to_move;
+}
+
+/// Included in the key for each castle right that is still avaliable.
+pub fn castle_right_key(castle_right: usize) -> u64 {
+    return ZOBRIST_KEYS.castle_rights[castle_right];
+}
+
+/// Included in the key when there is an en passant square. Files start at 1.
+pub fn en_passant_key(file: usize) -> u64 {
+    return ZOBRIST_KEYS.en_passant_files[file - 1];
+}
+
+const fn generate_keys(seed: u64) -> ZobristKeys {
+    let mut keys = ZobristKeys {
+        pieces: [[0; 64]; 12],
+        black_to_move: 0,
+        castle_rights: [0; 4],
+        en_passant_files: [0; 8],
+    };
+
+    let mut state = seed;
+    let mut piece_inx = 0;
+    while piece_inx < 12 {
+        let mut square_inx = 0;
+        while square_inx < 64 {
+            state = splitmix64_next(state);
+            keys.pieces[piece_inx][square_inx] = splitmix64_mix(state);
+            square_inx += 1;
+        }
+        piece_inx += 1;
+    }
+
+    state = splitmix64_next(state);
+    keys.black_to_move = splitmix64_mix(state);
+
+    let mut i = 0;
+    while i < 4 {
+        state = splitmix64_next(state);
+        keys.castle_rights[i] = splitmix64_mix(state);
+        i += 1;
+    }
+
+    i = 0;
+    while i < 8 {
+        state = splitmix64_next(state);
+        keys.en_passant_files[i] = splitmix64_mix(state);
+        i += 1;
+    }
+
+    return keys;
+}
+
+/// SplitMix64 pseudo random number generator.
+/// https://prng.di.unimi.it/splitmix64.c
+const fn splitmix64_next(state: u64) -> u64 {
+    return state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+}
+
+const fn splitmix64_mix(state: u64) -> u64 {
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    return z ^ (z >> 31);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::zobrist::{piece_key, black_to_move_key, castle_right_key, en_passant_key};
+    use crate::types::{Square, Piece};
+    use std::collections::HashSet;
+
+    #[test]
+    fn keys_are_unique() {
+        let mut keys = HashSet::new();
+        for piece in "PNBRQKpnbrqk".chars() {
+            for square_inx in 0..64 {
+                keys.insert(piece_key(Piece::from_char(piece).unwrap(), Square::from_index(square_inx)));
+            }
+        }
+        keys.insert(black_to_move_key());
+        for castle_right in 0..4 {
+            keys.insert(castle_right_key(castle_right));
+        }
+        for file in 1..=8 {
+            keys.insert(en_passant_key(file));
+        }
+
+        assert_eq!( keys.len(), 12*64 + 1 + 4 + 8 );
+        assert!( !keys.contains(&0) );
+    }
+}